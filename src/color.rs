@@ -0,0 +1,221 @@
+use std::{env, str::FromStr};
+
+/// The set of colors the terminal is able to display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    /// 24-bit RGB via ANSI Truecolor sequences.
+    TrueColor,
+    /// The xterm 256-color palette.
+    Ansi256,
+    /// The 16 basic ANSI colors, for the oldest terminals and serial consoles. The bright half uses the widely
+    /// supported `9X`/`10X` codes.
+    Ansi16,
+}
+
+impl ColorMode {
+    /// Guesses the terminal's color support from the environment.
+    ///
+    /// There's no reliable way to query this, so it follows the same conventions most terminal programs do:
+    /// `$COLORTERM` advertises truecolor, and `$TERM`/`$TERM_PROGRAM` identify terminals known to support it.
+    pub fn detect() -> Self {
+        // The Windows console has supported truecolor since ANSI escapes were added to it
+        if cfg!(target_os = "windows") {
+            return ColorMode::TrueColor;
+        }
+
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let term = env::var("TERM").unwrap_or_default();
+        let term_program = env::var("TERM_PROGRAM").unwrap_or_default();
+
+        if ["truecolor", "24bit"].contains(&colorterm.as_str())
+            || term.contains("truecolor")
+            || term.contains("direct")
+            || ["iTerm.app", "WezTerm", "vscode"].contains(&term_program.as_str())
+        {
+            ColorMode::TrueColor
+        } else {
+            ColorMode::Ansi256
+        }
+    }
+
+    /// Escape sequence that sets the foreground color to the nearest displayable match of `rgb`.
+    pub fn fg(self, rgb: [u8; 3]) -> String {
+        match self {
+            ColorMode::TrueColor => format!("\x1b[38;2;{};{};{}m", rgb[0], rgb[1], rgb[2]),
+            ColorMode::Ansi256 => format!("\x1b[38;5;{}m", rgb_to_ansi256(rgb)),
+            ColorMode::Ansi16 => format!("\x1b[{}m", ansi16_code(rgb_to_ansi16(rgb), 30)),
+        }
+    }
+
+    /// Escape sequence that sets the background color to the nearest displayable match of `rgb`.
+    pub fn bg(self, rgb: [u8; 3]) -> String {
+        match self {
+            ColorMode::TrueColor => format!("\x1b[48;2;{};{};{}m", rgb[0], rgb[1], rgb[2]),
+            ColorMode::Ansi256 => format!("\x1b[48;5;{}m", rgb_to_ansi256(rgb)),
+            ColorMode::Ansi16 => format!("\x1b[{}m", ansi16_code(rgb_to_ansi16(rgb), 40)),
+        }
+    }
+
+    /// The color the terminal actually shows when asked for `rgb`.
+    pub(crate) fn displayed(self, rgb: [u8; 3]) -> [u8; 3] {
+        match self {
+            ColorMode::TrueColor => rgb,
+            ColorMode::Ansi256 => ansi256_to_rgb(rgb_to_ansi256(rgb)),
+            ColorMode::Ansi16 => ANSI16[rgb_to_ansi16(rgb) as usize],
+        }
+    }
+}
+
+impl FromStr for ColorMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "truecolor" | "24bit" => Ok(ColorMode::TrueColor),
+            "256" => Ok(ColorMode::Ansi256),
+            "16" => Ok(ColorMode::Ansi16),
+            _ => Err(format!(
+                "unknown color mode '{}': expected one of truecolor, 256, 16",
+                s
+            )),
+        }
+    }
+}
+
+/// The 16 basic ANSI colors, as xterm draws them by default.
+const ANSI16: [[u8; 3]; 16] = [
+    [0, 0, 0],
+    [205, 0, 0],
+    [0, 205, 0],
+    [205, 205, 0],
+    [0, 0, 238],
+    [205, 0, 205],
+    [0, 205, 205],
+    [229, 229, 229],
+    [127, 127, 127],
+    [255, 0, 0],
+    [0, 255, 0],
+    [255, 255, 0],
+    [92, 92, 255],
+    [255, 0, 255],
+    [0, 255, 255],
+    [255, 255, 255],
+];
+
+/// Guesses the terminal's background color from `$COLORFGBG`.
+///
+/// Terminals like rxvt and Konsole set it to `FG;BG` (sometimes `FG;default;BG`), where each is an index into the basic 16 colors.
+/// Returns `None` if it isn't set or can't be parsed, which is the case for most terminals.
+pub fn terminal_background() -> Option<[u8; 3]> {
+    let colorfgbg = env::var("COLORFGBG").ok()?;
+    let idx: usize = colorfgbg.rsplit(';').next()?.parse().ok()?;
+    ANSI16.get(idx).copied()
+}
+
+/// Channel values of the 6x6x6 color cube making up indices 16-231 of the xterm palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Maps an RGB color to the closest entry of the xterm 256-color palette.
+///
+/// Only the color cube and the grayscale ramp are considered, since the first 16 colors are commonly redefined by terminal themes.
+fn rgb_to_ansi256(rgb: [u8; 3]) -> u8 {
+    // Nearest cube level for each channel. The levels aren't evenly spaced, so search rather than divide.
+    let cube_idx = rgb.map(|c| {
+        CUBE_LEVELS
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, &l)| (l as i32 - c as i32).abs())
+            .unwrap()
+            .0
+    });
+    let cube_rgb = cube_idx.map(|i| CUBE_LEVELS[i]);
+
+    // The grayscale ramp runs from 8 to 238 in steps of 10 over indices 232-255
+    let avg = (rgb.iter().map(|&c| c as u32).sum::<u32>() / 3) as u8;
+    let gray_idx = (avg.saturating_sub(3) / 10).min(23);
+    let gray_level = 8 + gray_idx * 10;
+
+    if distance(rgb, [gray_level; 3]) < distance(rgb, cube_rgb) {
+        232 + gray_idx
+    } else {
+        (16 + 36 * cube_idx[0] + 6 * cube_idx[1] + cube_idx[2]) as u8
+    }
+}
+
+/// Maps an RGB color to the closest of the 16 basic ANSI colors.
+fn rgb_to_ansi16(rgb: [u8; 3]) -> u8 {
+    (0..16u8)
+        .min_by_key(|&i| distance(rgb, ANSI16[i as usize]))
+        .unwrap()
+}
+
+/// The SGR parameter for basic color `idx`, where `base` is 30 for the foreground or 40 for the background.
+/// The bright colors (8-15) come 60 later.
+fn ansi16_code(idx: u8, base: u8) -> u8 {
+    match idx {
+        0..=7 => base + idx,
+        _ => base + 60 + idx - 8,
+    }
+}
+
+/// Looks up the color of an entry in the xterm 256-color palette.
+fn ansi256_to_rgb(idx: u8) -> [u8; 3] {
+    match idx {
+        0..=15 => ANSI16[idx as usize],
+        16..=231 => {
+            let i = (idx - 16) as usize;
+            [
+                CUBE_LEVELS[i / 36],
+                CUBE_LEVELS[i / 6 % 6],
+                CUBE_LEVELS[i % 6],
+            ]
+        }
+        _ => [8 + (idx - 232) * 10; 3],
+    }
+}
+
+/// Squared euclidean distance between two colors.
+pub(crate) fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter()
+        .zip(b.iter())
+        .map(|(&x, &y)| (x as i32 - y as i32).pow(2) as u32)
+        .sum()
+}
+
+/// Parses a hex color in the format `RRGGBB`, optionally prefixed with `#`.
+///
+/// # Errors
+/// Fails unless given exactly six hex digits.
+pub fn parse_color(s: &str) -> Result<[u8; 3], String> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("'{}' is not a color in the format RRGGBB", s));
+    }
+
+    // Every character is an ASCII hex digit, so slicing and parsing can't fail
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+    Ok([channel(0), channel(2), channel(4)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palette_colors_map_to_themselves() {
+        for idx in 16..=255 {
+            assert_eq!(rgb_to_ansi256(ansi256_to_rgb(idx)), idx);
+        }
+    }
+
+    #[test]
+    fn basic_colors_use_their_own_codes() {
+        assert_eq!(ColorMode::Ansi16.fg([250, 10, 10]), "\x1b[91m");
+        assert_eq!(ColorMode::Ansi16.bg([190, 5, 0]), "\x1b[41m");
+        assert_eq!(ColorMode::Ansi16.bg([10, 10, 10]), "\x1b[40m");
+        assert_eq!(
+            ColorMode::Ansi16.displayed([250, 250, 250]),
+            [255, 255, 255]
+        );
+    }
+}
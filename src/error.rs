@@ -0,0 +1,110 @@
+use std::{fmt::Display, io, path::PathBuf, time::Duration};
+
+use image::ImageError;
+use thiserror::Error;
+
+/// Everything that can go wrong while loading, transforming or playing a file.
+#[derive(Debug, Error)]
+pub enum Png2tError {
+    /// ffmpeg or ffprobe couldn't be found. `flag` is the option that points png2t at it.
+    #[error("Could not find {name} at {}! Please install first, ensure it is on your PATH, or point {flag} at it.", .path.display())]
+    FfmpegNotFound {
+        name: &'static str,
+        path: PathBuf,
+        flag: &'static str,
+    },
+
+    /// A file given to png2t doesn't exist.
+    #[error("{0} does not exist")]
+    FileNotFound(String),
+
+    /// A file, or something unpacked from it, couldn't be read as media.
+    #[error("Unable to decode {what}: {reason}")]
+    Decode { what: String, reason: String },
+
+    /// ffmpeg managed to unpack the file, but there was no picture in it.
+    #[error("No video stream found in {0}: only images and videos can be rendered")]
+    NoVideoStream(String),
+
+    /// Audio was asked for, but the file doesn't have any (or it wasn't extracted).
+    #[error("No audio stream found in {0}")]
+    NoAudioStream(String),
+
+    /// ffmpeg was still unpacking `file` when `--timeout` ran out.
+    #[error("Gave up on unpacking {file} after {}s. Use --timeout to allow longer.", .timeout.as_secs_f32())]
+    Timeout { file: String, timeout: Duration },
+
+    /// An option, or combination of them, that can't be acted on.
+    #[error("{0}")]
+    InvalidArgs(String),
+
+    /// The audio device couldn't be opened or played on.
+    #[error("Unable to play audio: {0}")]
+    Audio(String),
+
+    /// An image couldn't be encoded, such as when saving frames.
+    #[error("{context}: {source}")]
+    Encode {
+        context: String,
+        #[source]
+        source: ImageError,
+    },
+
+    /// Reading or writing something failed. `context` says what was being done.
+    #[error("{context}: {source}")]
+    Io {
+        context: String,
+        #[source]
+        source: io::Error,
+    },
+}
+
+impl Png2tError {
+    /// The code png2t exits with for this error, so that scripts can tell the causes apart.
+    ///
+    /// 2 is shared with invalid arguments, which is what clap already exits with for those.
+    pub fn code(&self) -> i32 {
+        match self {
+            Png2tError::FfmpegNotFound { .. } | Png2tError::InvalidArgs(_) => 2,
+            Png2tError::FileNotFound(_) => 3,
+            Png2tError::Decode { .. }
+            | Png2tError::NoVideoStream(_)
+            | Png2tError::Timeout { .. } => 4,
+            _ => 1,
+        }
+    }
+
+    /// Shorthand for a `Decode` error.
+    pub(crate) fn decode(what: impl Into<String>, reason: impl Display) -> Self {
+        Png2tError::Decode {
+            what: what.into(),
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Shorthand for an `Io` error.
+    pub(crate) fn io(context: impl Into<String>, source: io::Error) -> Self {
+        Png2tError::Io {
+            context: context.into(),
+            source,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_group_causes() {
+        let missing = Png2tError::FfmpegNotFound {
+            name: "ffmpeg",
+            path: PathBuf::from("ffmpeg"),
+            flag: "--ffmpeg-path",
+        };
+        assert_eq!(missing.code(), 2);
+        assert_eq!(Png2tError::FileNotFound(String::from("a.png")).code(), 3);
+        assert_eq!(Png2tError::decode("a.png", "bad header").code(), 4);
+        assert_eq!(Png2tError::NoAudioStream(String::from("a.png")).code(), 1);
+    }
+}
@@ -1,414 +1,2404 @@
 use std::{
+    borrow::Cow,
+    collections::hash_map::DefaultHasher,
+    ffi::OsStr,
     fs::{self, File},
-    io::{BufReader, Write},
-    path::PathBuf,
-    process::{Command, Stdio},
-    time::Duration,
+    hash::{Hash, Hasher},
+    io::{self, BufRead, BufReader, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::{self, Child, Command, ExitStatus, Stdio},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{sync_channel, RecvTimeoutError},
+        Once, OnceLock,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use crossterm::{
-    cursor::{position, MoveDown, MoveTo, MoveToColumn, MoveUp},
-    event::{poll, read, Event, KeyCode, KeyEvent, KeyModifiers},
-    terminal::{disable_raw_mode, enable_raw_mode},
+    cursor::{position, MoveTo, MoveToColumn},
+    event::{poll, read, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute,
+    style::{Color, ResetColor, SetForegroundColor},
+    terminal::{
+        disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen,
+        LeaveAlternateScreen,
+    },
 };
 use image::{
-    imageops::{flip_horizontal_in_place, flip_vertical_in_place, resize, FilterType::Nearest},
-    ImageBuffer, Rgba,
+    codecs::{png::PngDecoder, webp::WebPDecoder},
+    imageops::resize,
+    io::Reader as ImageReader,
+    AnimationDecoder, ImageBuffer, ImageError, ImageFormat, Rgba,
 };
 use itertools::Itertools;
-use lazy_static::lazy_static;
-use regex::Regex;
-use rodio::{OutputStream, OutputStreamHandle};
+use rodio::{Decoder, OutputStream, Sink};
 
-use crate::Args;
+use crate::{
+    color::{self, ColorMode},
+    error::Png2tError,
+    iterm::render_iterm,
+    lut::Lut,
+    palette::Palette,
+    probe::Probe,
+    record::record_gif,
+    render::{
+        inverted_if, render_ascii, render_image, render_image_diff, render_json, Glyph,
+        RenderOptions, ASCII_RAMP,
+    },
+    subtitles::{active_cue, load_srt, Cue},
+    tonemap::{self, Tonemap},
+    transform::{fit_within, transform_frames, Fit, TransformConfig, FILTER},
+    tty::terminal_size,
+};
+
+#[cfg(unix)]
+use crate::suspend::suspend;
 
 pub type Image = ImageBuffer<Rgba<u8>, Vec<u8>>;
 
+/// Framerate to play videos at when none is given and none can be detected.
+const DEFAULT_FPS: f32 = 24.0;
+
+/// Written into a cache directory once it holds everything extracted from a file.
+const CACHE_MARKER: &str = "complete";
+
+/// How often to check on ffmpeg while it's unpacking, which is also how often the progress bar updates.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Longest to wait for a keypress in one go while a video frame is held, before checking whether it's time for the next.
+const EVENT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Width of the unpacking progress bar, in characters.
+const PROGRESS_WIDTH: u64 = 30;
+
+/// How many times `Media::bench` renders the frame for each measurement.
+const BENCH_ITERATIONS: u32 = 100;
+
+/// Color of the box around the picked frame of an interactive contact sheet.
+const SELECTION_COLOR: Color = Color::Yellow;
+
+/// Counts up to give every `Media` in this process its own temporary directory.
+static NEXT_TMP_DIR: AtomicUsize = AtomicUsize::new(0);
+
+/// Everything that controls how a `Media` is loaded, transformed and played. Built up by `MediaBuilder`.
+#[derive(Debug, Clone, Default)]
+struct Config {
+    file: String,
+    color_mode: Option<ColorMode>,
+    background: Option<[u8; 3]>,
+    transparent: Option<[char; 2]>,
+    glyph: Glyph,
+    best_fit: bool,
+    fps: Option<f32>,
+    target_fps: Option<f32>,
+    frame_delay: Option<Duration>,
+    loop_video: bool,
+    loop_count: Option<u32>,
+    loop_delay: Option<Duration>,
+    fade: Option<Duration>,
+    mute: bool,
+    skip_audio: bool,
+    cache: bool,
+    keep_temp: bool,
+    temp_dir: Option<PathBuf>,
+    center: bool,
+    no_scroll: bool,
+    iterm: bool,
+    ascii: bool,
+    ascii_ramp: Vec<char>,
+    invert: bool,
+    linear_invert: bool,
+    step: bool,
+    interactive: bool,
+    timing: bool,
+    alt_screen: bool,
+    max_frames: Option<u32>,
+    repeat: Option<u32>,
+    seek: Option<Duration>,
+    audio_track: Option<usize>,
+    video_track: Option<usize>,
+    tonemap: Tonemap,
+    subtitles: Option<PathBuf>,
+    embedded_subtitles: bool,
+    timeout: Option<Duration>,
+    ffmpeg: PathBuf,
+    ffprobe: PathBuf,
+}
+
+/// Builder for a `Media`, for use without going through the command line.
+///
+/// Every option defaults to off, matching png2t run with no flags.
+#[derive(Debug, Clone)]
+pub struct MediaBuilder {
+    config: Config,
+}
+
+impl MediaBuilder {
+    /// Starts building a `Media` for the file at `file`.
+    pub fn new(file: impl Into<String>) -> Self {
+        MediaBuilder {
+            config: Config {
+                file: file.into(),
+                ffmpeg: PathBuf::from("ffmpeg"),
+                ffprobe: PathBuf::from("ffprobe"),
+                ascii_ramp: ASCII_RAMP.chars().collect(),
+                ..Config::default()
+            },
+        }
+    }
+
+    /// Invert all color. Can also be toggled with `i` while a video plays.
+    pub fn invert(mut self, invert: bool) -> Self {
+        self.config.invert = invert;
+        self
+    }
+
+    /// Invert in linear light rather than on the sRGB values, whenever colors are inverted.
+    /// Midtones then come out lighter, which is physically correct but looks different from most inverts.
+    pub fn linear_invert(mut self, linear_invert: bool) -> Self {
+        self.config.linear_invert = linear_invert;
+        self
+    }
+
+    /// Colors to render with. Detected from the terminal when `None`.
+    pub fn color_mode(mut self, color_mode: Option<ColorMode>) -> Self {
+        self.config.color_mode = color_mode;
+        self
+    }
+
+    /// Color to fill in transparent pixels with. Transparent pixels are left blank when `None`.
+    pub fn background(mut self, background: Option<[u8; 3]>) -> Self {
+        self.config.background = background;
+        self
+    }
+
+    /// Draw fully transparent cells with these characters, alternating between them in a checkerboard,
+    /// instead of leaving them blank.
+    pub fn transparent(mut self, transparent: Option<[char; 2]>) -> Self {
+        self.config.transparent = transparent;
+        self
+    }
+
+    /// Which half-block character to draw cells with where both of their pixels are showing.
+    pub fn glyph(mut self, glyph: Glyph) -> Self {
+        self.config.glyph = glyph;
+        self
+    }
+
+    /// Pick each cell's glyph and colors to best match the picture, at some cost in drawing speed.
+    /// See `RenderOptions::best_fit`.
+    pub fn best_fit(mut self, best_fit: bool) -> Self {
+        self.config.best_fit = best_fit;
+        self
+    }
+
+    /// Framerate to play videos at. Detected with `ffprobe` when `None`.
+    pub fn fps(mut self, fps: Option<f32>) -> Self {
+        self.config.fps = fps;
+        self
+    }
+
+    /// Redraw videos at this rate regardless of their own timing, holding (or dropping) frames to match.
+    /// Makes slow videos respond to keypresses more quickly. Leaves the timing alone when `None`.
+    pub fn target_fps(mut self, target_fps: Option<f32>) -> Self {
+        self.config.target_fps = target_fps;
+        self
+    }
+
+    /// Hold every frame of a video for exactly this long, instead of going by its framerate.
+    pub fn frame_delay(mut self, frame_delay: Option<Duration>) -> Self {
+        self.config.frame_delay = frame_delay;
+        self
+    }
+
+    /// Loop videos forever.
+    pub fn loop_video(mut self, loop_video: bool) -> Self {
+        self.config.loop_video = loop_video;
+        self
+    }
+
+    /// Play videos this many times. Ignored if `loop_video` is set.
+    pub fn loop_count(mut self, loop_count: Option<u32>) -> Self {
+        self.config.loop_count = loop_count;
+        self
+    }
+
+    /// Pause on the last frame for this long between loops of a video, instead of starting over straight away.
+    pub fn loop_delay(mut self, loop_delay: Option<Duration>) -> Self {
+        self.config.loop_delay = loop_delay;
+        self
+    }
+
+    /// Fade videos in from black over this long at the start of playback, and back out to black at the end.
+    ///
+    /// Each loop fades in and out again.
+    pub fn fade(mut self, fade: Option<Duration>) -> Self {
+        self.config.fade = fade;
+        self
+    }
+
+    /// Don't play audio, even if it was extracted.
+    pub fn mute(mut self, mute: bool) -> Self {
+        self.config.mute = mute;
+        self
+    }
+
+    /// Don't extract audio at all.
+    pub fn skip_audio(mut self, skip_audio: bool) -> Self {
+        self.config.skip_audio = skip_audio;
+        self
+    }
+
+    /// Only unpack this many frames from the start of a video.
+    pub fn max_frames(mut self, max_frames: Option<u32>) -> Self {
+        self.config.max_frames = max_frames;
+        self
+    }
+
+    /// Print a still image this many times, one under another, such as for a banner. Videos ignore it.
+    pub fn repeat(mut self, repeat: Option<u32>) -> Self {
+        self.config.repeat = repeat;
+        self
+    }
+
+    /// Start this far into a video, skipping everything before it. Together with `max_frames(Some(1))`, only the
+    /// frame at this point is unpacked.
+    pub fn seek(mut self, seek: Option<Duration>) -> Self {
+        self.config.seek = seek;
+        self
+    }
+
+    /// Which audio stream to play, counting from 0. Uses ffmpeg's default pick when `None`.
+    pub fn audio_track(mut self, audio_track: Option<usize>) -> Self {
+        self.config.audio_track = audio_track;
+        self
+    }
+
+    /// Which video stream to render, counting from 0. Uses ffmpeg's default pick when `None`.
+    /// Files can carry more than one, such as a thumbnail alongside the movie itself.
+    pub fn video_track(mut self, video_track: Option<usize>) -> Self {
+        self.config.video_track = video_track;
+        self
+    }
+
+    /// How to bring the highlights of HDR sources (EXR and HDR images, and HDR videos) down into what a terminal can
+    /// show. 8-bit and 16-bit sources aren't affected.
+    pub fn tonemap(mut self, tonemap: Tonemap) -> Self {
+        self.config.tonemap = tonemap;
+        self
+    }
+
+    /// SubRip file to show subtitles from underneath videos as they play, timed from the start of the file.
+    pub fn subtitles(mut self, subtitles: Option<PathBuf>) -> Self {
+        self.config.subtitles = subtitles;
+        self
+    }
+
+    /// Show the file's own first subtitle track underneath videos as they play, if it has one.
+    /// Ignored if `subtitles` gives a file to show instead.
+    pub fn embedded_subtitles(mut self, embedded_subtitles: bool) -> Self {
+        self.config.embedded_subtitles = embedded_subtitles;
+        self
+    }
+
+    /// Keep extracted frames around between runs, and reuse them instead of running ffmpeg again.
+    pub fn cache(mut self, cache: bool) -> Self {
+        self.config.cache = cache;
+        self
+    }
+
+    /// Leave the extracted frames and audio behind when the `Media` is dropped, and print where they are.
+    pub fn keep_temp(mut self, keep_temp: bool) -> Self {
+        self.config.keep_temp = keep_temp;
+        self
+    }
+
+    /// Existing directory to extract frames and audio under, such as a RAM disk, instead of next to the executable.
+    ///
+    /// Each `Media` gets its own subdirectory in it, and only that is removed when it's dropped.
+    /// Ignored with `cache`, since cached frames always go in the cache directory.
+    pub fn temp_dir(mut self, temp_dir: Option<PathBuf>) -> Self {
+        self.config.temp_dir = temp_dir;
+        self
+    }
+
+    /// Center frames horizontally in the terminal instead of drawing them against the left edge.
+    pub fn center(mut self, center: bool) -> Self {
+        self.config.center = center;
+        self
+    }
+
+    /// Draw frames as iTerm2 inline images instead of colored characters.
+    pub fn iterm(mut self, iterm: bool) -> Self {
+        self.config.iterm = iterm;
+        self
+    }
+
+    /// Draw from wherever the cursor is without scrolling the terminal to make room first, even if frames run off the bottom.
+    pub fn no_scroll(mut self, no_scroll: bool) -> Self {
+        self.config.no_scroll = no_scroll;
+        self
+    }
+
+    /// Draw frames as plain ASCII art, without any color.
+    pub fn ascii(mut self, ascii: bool) -> Self {
+        self.config.ascii = ascii;
+        self
+    }
+
+    /// Characters to draw ASCII art with, from darkest to brightest. Defaults to `ASCII_RAMP`.
+    /// There must be at least two, or `build` fails.
+    pub fn ascii_ramp(mut self, ascii_ramp: Option<Vec<char>>) -> Self {
+        if let Some(ramp) = ascii_ramp {
+            self.config.ascii_ramp = ramp;
+        }
+        self
+    }
+
+    /// Step through videos one frame at a time with the arrow keys, instead of playing them. Audio isn't played.
+    pub fn step(mut self, step: bool) -> Self {
+        self.config.step = step;
+        self
+    }
+
+    /// Keep still images on screen until the user quits, redrawing them to fit whenever the terminal is resized.
+    pub fn interactive(mut self, interactive: bool) -> Self {
+        self.config.interactive = interactive;
+        self
+    }
+
+    /// Log how long each frame of a video took to draw, and how far apart frames were actually shown, to stderr.
+    pub fn timing(mut self, timing: bool) -> Self {
+        self.config.timing = timing;
+        self
+    }
+
+    /// Draw on the terminal's alternate screen, so that whatever was on screen before comes back once drawing is done.
+    ///
+    /// Only applies to what stays on screen until it's done playing or the user quits. A still image or contact sheet
+    /// that isn't `interactive` would be gone as soon as it was drawn, so it's drawn on the normal screen instead.
+    pub fn alt_screen(mut self, alt_screen: bool) -> Self {
+        self.config.alt_screen = alt_screen;
+        self
+    }
+
+    /// Give up on unpacking if ffmpeg takes longer than this. Waits forever when `None`.
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.config.timeout = timeout;
+        self
+    }
+
+    /// The `ffmpeg` binary to run. Defaults to whichever one is on the `PATH`.
+    pub fn ffmpeg(mut self, ffmpeg: impl Into<PathBuf>) -> Self {
+        self.config.ffmpeg = ffmpeg.into();
+        self
+    }
+
+    /// The `ffprobe` binary to run. Defaults to whichever one is on the `PATH`.
+    pub fn ffprobe(mut self, ffprobe: impl Into<PathBuf>) -> Self {
+        self.config.ffprobe = ffprobe.into();
+        self
+    }
+
+    /// Creates the `Media`, setting up its temporary storage.
+    ///
+    /// # Errors
+    /// Fails if the temporary directory can't be created, or if caching is enabled but the file can't be found.
+    /// Also fails if `ascii_ramp` was given fewer than two characters.
+    pub fn build(self) -> Result<Media, Png2tError> {
+        Media::new(self.config)
+    }
+}
+
 /// A wrapper for a media file.
 ///
 /// This struct can represent a video of any length and stores it internally.
 /// An external temporary directory is used to store media when creating an instance.
 /// The `Drop` trait is implemented to clear this temp directory.
 #[derive(Debug)]
-pub struct Media<'args> {
+pub struct Media {
     frames: Vec<Image>,
-    config: &'args Args,
+    config: Config,
     storage: PathBuf,
+    probe: Result<Probe, Png2tError>,
+    /// How long to show each frame for, when the source records it. Empty means play at a constant framerate.
+    delays: Vec<Duration>,
     is_video: bool,
     has_audio: bool,
+    /// Set for named pipes, whose frames are read, transformed and drawn as they arrive rather than unpacked up front.
+    /// Holds the transform to apply to each of them.
+    live: Option<TransformConfig>,
+    /// The palette frames were snapped to when they were transformed, which inverting them has to keep to.
+    palette: Option<Palette>,
+    /// Subtitles to show while playing, sorted by when they start.
+    cues: Vec<Cue>,
+    /// How far into the file the first frame comes, which is what subtitles are timed against.
+    start: Duration,
 }
 
-impl<'args> Media<'args> {
-    pub fn new(config: &'args Args) -> Result<Self, String> {
-        let storage = Self::get_tmp_dir();
+impl Media {
+    fn new(config: Config) -> Result<Self, Png2tError> {
+        // The darkest and brightest characters can't be one and the same
+        if config.ascii_ramp.len() < 2 {
+            return Err(Png2tError::InvalidArgs(format!(
+                "An ASCII ramp needs at least 2 characters, found {}",
+                config.ascii_ramp.len()
+            )));
+        }
+
+        let storage = match config.cache {
+            true => Self::get_cache_dir(&config)?,
+            false => Self::get_tmp_dir(&config)?,
+        };
 
         if !storage.exists() {
-            if let Err(e) = fs::create_dir(&storage) {
-                return Err(format!(
-                    "Unable to create output directory at {}: {}",
-                    storage.display(),
-                    e
+            if let Err(e) = fs::create_dir_all(&storage) {
+                return Err(Png2tError::io(
+                    format!("Unable to create output directory at {}", storage.display()),
+                    e,
                 ));
             }
         }
 
-        Ok(Media::<'args> {
+        let cues = match &config.subtitles {
+            Some(path) => load_srt(path)?,
+            None => Vec::default(),
+        };
+
+        Ok(Media {
             frames: Vec::default(),
+            start: config.seek.unwrap_or_default(),
+            cues,
             config,
             storage,
+            probe: Ok(Probe::default()),
+            delays: Vec::default(),
             is_video: false,
             has_audio: false,
+            live: None,
+            palette: None,
         })
     }
 
     /// Unpacks the file specified in `self.config.file`
     ///
     /// This function takes every available frame from a media file and stores it as individual .pngs for display.
-    /// It will also create a .mp3 with the associated audio if available.
-    /// Storage location is whatever is returned by `Self::get_tmp_dir()`
+    /// HDR sources are stored as .exrs instead, so their highlights survive until they can be tone-mapped.
+    /// It will also create a .mp3 with the associated audio if available, and an .srt of its subtitles if asked for.
+    /// Storage location is whatever is returned by `Self::get_tmp_dir(&self.config)`
     ///
     /// # Errors
-    /// Generally the only failure possible at this point is ffmpeg not being installed, which will return an OS error 2.
-    pub fn unpack_file(&mut self) -> Result<(), String> {
-        // Separate out the individual frames
-        Command::new("ffmpeg")
-            .args([
-                "-hide_banner",
-                "-i",
+    /// Fails with `Png2tError::Timeout` if ffmpeg takes too long, or `Png2tError::NoVideoStream` if it found nothing to render.
+    /// Frames that can't be decoded and trouble with the storage directory are also reported.
+    pub fn unpack_file(&mut self) -> Result<(), Png2tError> {
+        // Probing would eat into whatever has been written to a pipe, so it's all left for `render` to read as it goes
+        if self.is_fifo() {
+            self.live = Some(TransformConfig::default());
+            return Ok(());
+        }
+
+        if self.load_animated_webp()? {
+            return Ok(());
+        }
+
+        // Plain still images don't need ffmpeg (or ffprobe) at all
+        if self.load_still()? {
+            return Ok(());
+        }
+
+        self.probe = Probe::with_video_track(
+            &self.config.ffprobe,
+            &self.config.file,
+            self.config.video_track.unwrap_or(0),
+        );
+        if let (Some(track), Ok(probe)) = (self.config.video_track, &self.probe) {
+            if track >= probe.video_tracks {
+                return Err(Png2tError::InvalidArgs(format!(
+                    "--video-track {} doesn't exist in {}, which has {} video track(s) counting from 0",
+                    track, self.config.file, probe.video_tracks
+                )));
+            }
+        }
+
+        // GIFs and the like can hold each frame for a different length of time, which a constant framerate can't capture.
+        // If their timing is known, frames are extracted one-to-one rather than duplicated to fit a constant rate.
+        // Timing is listed from the very start, so it can't be lined up with frames after a seek.
+        let durations = match (&self.probe, self.config.seek) {
+            (
+                Ok(Probe {
+                    variable_timing: true,
+                    ..
+                }),
+                None,
+            ) => Probe::frame_durations(
+                &self.config.ffprobe,
                 &self.config.file,
-                self.storage.join("frame%d.exr").to_str().unwrap(),
-                "-preset",
-                "ultrafast",
-            ])
+                self.config.video_track.unwrap_or(0),
+            )
+            .ok(),
+            _ => None,
+        }
+        .map(|mut d| {
+            if let Some(max) = self.config.max_frames {
+                d.truncate(max as usize);
+            }
+            d
+        });
+
+        let marker = self.storage.join(CACHE_MARKER);
+        if self.config.cache {
+            if marker.exists() {
+                // A previous run already extracted everything, so skip straight to loading it
+                self.has_audio = !self.config.skip_audio && self.storage.join("audio.mp3").exists();
+                self.load_embedded_subtitles()?;
+                self.load_frames()?;
+                self.use_durations(durations);
+                return Ok(());
+            }
+
+            // Anything here without the marker is left over from an interrupted run, and ffmpeg won't overwrite it
+            self.clear_storage()?;
+        }
+
+        // Both ffmpeg runs share one time limit
+        let deadline = self.config.timeout.map(|t| Instant::now() + t);
+
+        // Separate out the individual frames
+        let output = self
+            .storage
+            .join(format!("frame%d.{}", self.frame_extension()));
+        // Seeking before the input lets ffmpeg jump straight there instead of decoding everything up to it
+        let seek = self.config.seek.map(|s| s.as_secs_f64().to_string());
+        let mut args = vec!["-hide_banner"];
+        if let Some(seek) = &seek {
+            args.extend(["-ss", seek]);
+        }
+        args.extend(["-i", &self.config.file]);
+        let map = self
+            .config
+            .video_track
+            .map(|track| format!("0:v:{}", track));
+        if let Some(map) = &map {
+            args.extend(["-map", map]);
+        }
+        // Keeps every frame as it is rather than evening out their timing. The option was renamed in ffmpeg 5.1.
+        if durations.is_some() {
+            match supports_fps_mode(&self.config.ffmpeg) {
+                true => args.extend(["-fps_mode", "passthrough"]),
+                false => args.extend(["-vsync", "passthrough"]),
+            }
+        }
+        let max_frames = self.config.max_frames.map(|n| n.to_string());
+        if let Some(n) = &max_frames {
+            args.extend(["-frames:v", n]);
+        }
+        args.extend([output.to_str().unwrap(), "-preset", "ultrafast"]);
+        let mut ffmpeg = match Command::new(&self.config.ffmpeg)
+            .args(args)
             .stdout(Stdio::null())
             .stderr(Stdio::null())
             .spawn()
-            .unwrap()
-            .wait()
-            .unwrap();
+        {
+            Ok(child) => child,
+            Err(e) => return Err(Png2tError::io("Unable to run ffmpeg", e)),
+        };
+
+        // Exact if per-frame timing was found, otherwise whatever ffprobe knows or can be estimated
+        let total = match (&durations, &self.probe) {
+            (Some(d), _) => Some(d.len() as u64),
+            (None, Ok(probe)) => {
+                let seek = self.config.seek.unwrap_or_default().as_secs_f32();
+                let estimate = || {
+                    let (duration, fps) = probe.duration.zip(probe.fps)?;
+                    Some(((duration - seek).max(0.0) * fps).round() as u64)
+                };
+                match self.config.seek {
+                    Some(_) => estimate(),
+                    None => probe.frames.or_else(estimate),
+                }
+            }
+            (None, Err(_)) => None,
+        }
+        .map(|total| match self.config.max_frames {
+            Some(max) => total.min(max as u64),
+            None => total,
+        });
+        self.wait_with_progress(&mut ffmpeg, total, deadline)?;
+
+        // Pull out audio stream if present. If skip_audio is set, ignore audio and set to false.
+        self.has_audio = false;
+        if !self.config.skip_audio {
+            let output = self.storage.join("audio.mp3");
+            let map = self.audio_map();
+            let mut args = vec!["-hide_banner"];
+            if let Some(seek) = &seek {
+                args.extend(["-ss", seek]);
+            }
+            args.extend(["-i", &self.config.file]);
+            if let Some(map) = &map {
+                args.extend(["-map", map]);
+            }
+            args.extend([output.to_str().unwrap(), "-preset", "ultrafast"]);
 
-        // Pull out audio stream if present.
-        self.has_audio = !self.config.mute && // If mute is set, ignore audio and set to false.
-            Command::new("ffmpeg")
-                .args([
-                    "-hide_banner",
-                    "-i",
-                    &self.config.file,
-                    self.storage.join("audio.mp3").to_str().unwrap(),
-                    "-preset",
-                    "ultrafast",
-                ])
+            let mut ffmpeg = match Command::new(&self.config.ffmpeg)
+                .args(args)
                 .stdout(Stdio::null())
                 .stderr(Stdio::null())
                 .spawn()
-                .unwrap()
-                .wait()
-                .unwrap()
-                .success(); // Return whether or not the command succeeded.
+            {
+                Ok(child) => child,
+                Err(e) => return Err(Png2tError::io("Unable to run ffmpeg", e)),
+            };
 
-        self.load_frames()
-    }
+            let status = loop {
+                if let Some(status) = self.poll_ffmpeg(&mut ffmpeg, deadline)? {
+                    break status;
+                }
+                thread::sleep(POLL_INTERVAL);
+            };
+            self.has_audio = status.success(); // Whether or not the command succeeded.
+        }
 
-    /// Read from `self.storage` and store every image in there in RGBA8 format into `self.frames`
-    ///
-    /// # Errors
-    /// Can either fail to access the temporary storage directory or individual files, or encounter an invalid PNG.
-    /// These issues are unlikely but could be caused by a race condition with another program modifying `self.storage` during execution.
-    fn load_frames(&mut self) -> Result<(), String> {
-        // Objective: get a list of all files in a directory in human-sorted order
-        let frames: Vec<PathBuf> = fs::read_dir(&self.storage) // gets all files in `&self.storage`
-            .unwrap()
-            .map(|r| String::from(r.unwrap().path().to_str().unwrap())) // Unwrap ReadDir into a DirEntry, which is still not a sortable plain string. Thus, pull the `path()` from it, then cast it to a string, then wrap it in `String::from()` for ownership reasons
-            .sorted_by(|a, b| human_sort::compare(a, b)) // Apply human-sort
-            .map(PathBuf::from) // Cast the list of sorted strings into Path objects instead
-            .filter(|p| p.extension().unwrap() == "exr")
-            .collect(); // Collect into the final vector
+        self.extract_subtitles(deadline)?;
+        self.load_embedded_subtitles()?;
+        self.load_frames()?;
+        self.use_durations(durations);
 
-        for (idx, frame) in frames.iter().enumerate() {
-            let reader = image::io::Reader::open(frame);
-            if let Err(e) = reader {
-                return Err(format!(
-                    "Unable to read from temp directory {}: {}",
-                    self.storage.display(),
-                    e
-                ));
-            }
-            let decoder = reader.unwrap().decode();
-            if let Err(e) = decoder {
-                return Err(format!(
-                    "Unable to decode {}: {}",
-                    if self.frames.len() == 1 {
-                        self.config.file.clone()
-                    } else {
-                        format!(
-                            "frame {}/{} of {}",
-                            idx,
-                            self.frames.len(),
-                            self.config.file
-                        )
-                    },
-                    e
+        if self.config.cache {
+            if let Err(e) = File::create(&marker) {
+                return Err(Png2tError::io(
+                    format!(
+                        "Unable to mark cache directory {} as complete",
+                        self.storage.display()
+                    ),
+                    e,
                 ));
             }
-
-            // Parse file into RGBA8 format and push it into `self.frames`
-            self.frames.push(decoder.unwrap().into_rgba8());
         }
 
-        self.is_video = self.frames.len() > 1;
-
         Ok(())
     }
 
-    /// Transform each frame based on command line flags
+    /// Has ffmpeg convert the file's first subtitle track to `subtitles.srt` in storage, if it's wanted and there is one.
     ///
-    /// Pulls all information from `self.config`.
-    /// This function has potential to be the slowest in the rendering process if done with too many flags - be careful in here
-    pub fn transform(&mut self) -> Result<(), String> {
-        let (mut nwidth, mut nheight) = self.frames[0].dimensions();
-
-        // The following block calculates the final image size. Multiple factors influence it so it's best to calculate it once.
-        // This means we can't support dynamically resizing .mp4s and such, but I think that's okay... (sorry Discord trolls)
-        if let Some(s) = &self.config.size {
-            let coords: Vec<u32> = s.split('x').map(|c| str::parse(c).unwrap_or(0)).collect();
-            if coords.contains(&0) {
-                return Err(String::from(
-                    "Invalid coordinates supplied to --size tag: must be in format NUMxNUM",
-                ));
-            }
-
-            nwidth = coords[0];
-            nheight = coords[1];
-        } else if !self.config.preserve_dims {
-            // Set the longest side to be 64px, with the shorter side scaling down proportionally to preserve aspect ratio
-            (nwidth, nheight) = match nwidth > nheight {
-                true => (64, (64f64 * (nheight as f64 / nwidth as f64)) as u32),
-                false => ((64f64 * (nwidth as f64 / nheight as f64)) as u32, 64),
-            };
+    /// The whole track is converted, without seeking, so its cues stay timed from the start of the file.
+    ///
+    /// # Errors
+    /// Fails if ffmpeg can't be run or runs past `deadline`.
+    fn extract_subtitles(&self, deadline: Option<Instant>) -> Result<(), Png2tError> {
+        if !self.config.embedded_subtitles || !self.cues.is_empty() {
+            return Ok(());
         }
-
-        if let Some(scale) = self.config.scale {
-            nwidth = (nwidth as f32 * scale) as u32;
-            nheight = (nheight as f32 * scale) as u32;
+        if let Ok(Probe {
+            subtitle_tracks: 0, ..
+        }) = self.probe
+        {
+            eprintln!("Warning: {} has no subtitles to show.", self.config.file);
+            return Ok(());
         }
 
-        for frame in &mut self.frames {
-            *frame = resize(frame, nwidth, nheight, Nearest);
-
-            for pixel in frame.chunks_exact_mut(4) {
-                if self.config.invert {
-                    pixel[0] = u8::MAX - pixel[0];
-                    pixel[1] = u8::MAX - pixel[1];
-                    pixel[2] = u8::MAX - pixel[2];
-                }
-            }
+        let output = self.storage.join("subtitles.srt");
+        let mut ffmpeg = match Command::new(&self.config.ffmpeg)
+            .args(["-hide_banner", "-i", &self.config.file, "-map", "0:s:0"])
+            .arg(&output)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => return Err(Png2tError::io("Unable to run ffmpeg", e)),
+        };
 
-            if self.config.flip_h {
-                flip_horizontal_in_place(frame)
+        let status = loop {
+            if let Some(status) = self.poll_ffmpeg(&mut ffmpeg, deadline)? {
+                break status;
             }
+            thread::sleep(POLL_INTERVAL);
+        };
 
-            if self.config.flip_v {
-                flip_vertical_in_place(frame)
-            }
+        // Picture-based subtitles (as on DVDs and Blu-rays) can't be turned into text
+        if !status.success() {
+            let _ = fs::remove_file(&output);
+            eprintln!(
+                "Warning: the subtitles in {} couldn't be converted to text, so won't be shown.",
+                self.config.file
+            );
         }
+        Ok(())
+    }
 
+    /// Loads the subtitles `extract_subtitles` left in storage, if there are any and they're wanted.
+    ///
+    /// # Errors
+    /// Fails if they can't be read or parsed.
+    fn load_embedded_subtitles(&mut self) -> Result<(), Png2tError> {
+        let path = self.storage.join("subtitles.srt");
+        if self.config.embedded_subtitles && self.cues.is_empty() && path.exists() {
+            self.cues = load_srt(&path)?;
+        }
         Ok(())
     }
 
-    /// Plays the media file in the terminal. Must be initialized with `self.load_frames()` first.
+    /// Decodes the file directly if it's an animated WebP, keeping each frame's duration.
+    ///
+    /// ffmpeg has historically been unable to decode animated WebPs at all, so they can't go through the usual unpacking.
+    ///
+    /// # Returns
+    /// `Ok(true)` if the file was loaded, or `Ok(false)` if it isn't an animated WebP and should be unpacked as usual.
     ///
     /// # Errors
-    /// Can error out if `self` contains a video but the FPS cannot be determined.
-    /// Also may fail on I/O or sound device errors.
-    /// Can possibly fail on file I/O, but is only possible by race condition with another program modifying the storage directory.
-    pub fn render(&self) -> Result<(), String> {
-        // Create buffer space in the terminal for the image before printing
-        let h = self.frames[0].dimensions().1 / 2;
-        for _ in 0..h {
-            println!();
+    /// Fails if the file is an animated WebP but one of its frames can't be decoded.
+    fn load_animated_webp(&mut self) -> Result<bool, Png2tError> {
+        let is_webp = Path::new(&self.config.file)
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("webp"));
+        if !is_webp {
+            return Ok(false);
         }
 
-        // Turn off the fancy stuff in the terminal. I'm using this to later emulate C's `getchar`
-        enable_raw_mode().unwrap();
+        // Anything that can't be opened here is left for ffmpeg to try (and report on)
+        let decoder = match File::open(&self.config.file)
+            .map_err(ImageError::IoError)
+            .and_then(|f| WebPDecoder::new(BufReader::new(f)))
+        {
+            Ok(d) if d.has_animation() => d,
+            _ => return Ok(false),
+        };
 
-        // Reset cursor to where the top-left pixel should print
-        print!("{}{}", MoveToColumn(0), MoveUp(h as u16));
+        let frames = match decoder.into_frames().collect_frames() {
+            Ok(f) => f,
+            Err(e) => return Err(Png2tError::decode(&self.config.file, e)),
+        };
 
-        // Save this location for quicker cursor resets when new frames are printed
-        let pos = position().unwrap();
+        // Skip every frame that finishes before the seek point, keeping the one showing at that moment
+        let seek = self.config.seek.unwrap_or_default();
+        let mut elapsed = Duration::ZERO;
+        let frames = frames.into_iter().skip_while(|frame| {
+            elapsed += Duration::from(frame.delay());
+            elapsed <= seek && seek > Duration::ZERO
+        });
 
-        // The code to play a video is a lot more complex, so it's not worthwhile to try to generalize this for photos vs. videos
-        if self.is_video {
-            // Following block uses regex to extract the video's fps from the output of `ffprobe`
-            lazy_static! {
-                static ref RE: Regex = Regex::new(r"(\d*\.?\d*) fps").unwrap();
-            }
-
-            let fps: f32;
-            if let Some(m) = RE
-                .captures(
-                    &String::from_utf8(
-                        Command::new("ffprobe")
-                            .args(["-hide_banner", "-i", &self.config.file])
-                            .output()
-                            .unwrap()
-                            .stderr,
-                    )
-                    .unwrap(),
-                )
-                .unwrap()
-                .get(1)
-            {
-                fps = str::parse(m.as_str()).unwrap();
-            } else {
-                return Err(String::from("Could not determine framerate of video!"));
-            }
+        let max = self.config.max_frames.map_or(usize::MAX, |n| n as usize);
+        for frame in frames.take(max) {
+            self.delays.push(frame.delay().into());
+            self.frames.push(frame.into_buffer());
+        }
 
-            // Based on the fps, calculate how long to wait between each frame printing
-            let delay = std::time::Duration::from_millis((1000.0 / fps) as u64);
+        self.is_video = self.frames.len() > 1;
+        Ok(true)
+    }
 
-            // Rust's deallocation methods kill the audio if it is in a separate block from the video rendering.
-            // This means it won't be able to play if we slim down on repeated code by only using this if/else tree to spawn the audio when true.
-            // This is my least favorite piece of code
-            loop {
-                // Spawn the audio and keep it from deallocating with `let`
-                let res = if self.has_audio {
-                    let _audio = self.spawn_audio();
-                    self.play_video(delay, pos)
-                } else {
-                    self.play_video(delay, pos)
-                };
+    /// Decodes the file directly if it's a still image the `image` crate can read, skipping the round-trip through ffmpeg.
+    ///
+    /// Anything that turns out not to decode (such as formats this build of `image` can't read) is left for ffmpeg
+    /// to try instead.
+    ///
+    /// # Returns
+    /// `Ok(true)` if the file was loaded, or `Ok(false)` if it should be unpacked with ffmpeg.
+    fn load_still(&mut self) -> Result<bool, Png2tError> {
+        let format = match still_format(&self.config.file) {
+            Some(f) => f,
+            None => return Ok(false),
+        };
 
-                // Keep playing if true, otherwise the user requested an early exit (or loop_video == false)
-                match res? {
-                    true => continue,
-                    false => break,
-                };
+        match image::open(&self.config.file) {
+            Ok(img) => self
+                .frames
+                .push(tonemap::to_rgba8(img, self.config.tonemap)),
+            Err(e) => {
+                eprintln!(
+                    "Warning: Unable to decode {} as {:?} ({}), trying ffmpeg instead.",
+                    self.config.file, format, e
+                );
+                return Ok(false);
             }
-        } else {
-            // If we just have an image, we simply gotta display it
-            self.display_frame(&self.frames[0])?;
         }
 
-        disable_raw_mode().unwrap();
-        Ok(())
+        self.is_video = false;
+        Ok(true)
     }
 
-    /// Interal function to display one image into the terminal.
+    /// Works out the ffmpeg `-map` argument that picks the requested audio track, if there is one.
     ///
-    /// # Errors
-    /// I/O errors can occur when flushing `stdout`
-    fn display_frame(&self, frame: &Image) -> Result<(), String> {
-        let (w, h) = frame.dimensions();
-        let (mut x, mut y) = (0u32, 0u32);
-        for _ in 0..(h / 2) * w {
-            let upper = frame.get_pixel(x, y);
-            let lower = frame.get_pixel(x, y + 1);
+    /// Tracks that don't exist are warned about and ignored, so that ffmpeg falls back to its default track.
+    fn audio_map(&self) -> Option<String> {
+        let track = self.config.audio_track?;
 
-            // This if/else is to handle image transparency, but the first case is the simplest to understand.
-            // Using the unicode ▄ symbol, we can use ANSI Truecolor to color its foreground and background.
-            // This means that each character in the terminal can represent two pixels, one higher and one lower.
-            // If [3] is 0 on a pixel, this means it should be transparent, so we leave the foreground/background uncolored
-            // or use other means to keep that pixel transparent.
-            if upper[3] != 0 && lower[3] != 0 {
-                print!(
-                    "\x1b[48;2;{};{};{}m\x1b[38;2;{};{};{}m▄\x1b[0m",
-                    upper[0], upper[1], upper[2], lower[0], lower[1], lower[2]
+        if let Ok(probe) = &self.probe {
+            if track >= probe.audio_tracks {
+                eprintln!(
+                    "Warning: --audio-track {} doesn't exist in {} (it has {} audio track(s), counting from 0), using the default instead.",
+                    track, self.config.file, probe.audio_tracks
                 );
-            } else if upper[3] == 0 && lower[3] == 0 {
-                print!(" ");
-            } else if upper[3] != 0 && lower[3] == 0 {
-                print!("\x1b[38;2;{};{};{}m▀\x1b[0m", upper[0], upper[1], upper[2]);
-            } else {
-                print!("\x1b[38;2;{};{};{}m▄\x1b[0m", lower[0], lower[1], lower[2]);
-            }
-
-            if let Err(e) = std::io::stdout().flush() {
-                return Err(format!("\nFailed to print image at ({}, {}): {}", x, y, e));
-            }
-
-            // Arithmetic to keep cursor in the right position to print
-            if x == w - 1 {
-                x = 0;
-                y += 2;
-                print!("{}{}", MoveDown(1), MoveToColumn(0));
-            } else {
-                x += 1;
+                return None;
             }
         }
 
-        Ok(())
+        Some(format!("0:a:{}", track))
     }
 
-    /// Plays a video stored in `self.frames`
+    /// Waits for ffmpeg to finish extracting frames, showing a progress bar on stderr while it works.
     ///
-    /// # Returns
-    /// `Ok(bool)` will be true if the video should continue playing.
-    /// This is only with regards to whether or not the user has attempted to "quit" the program, and does not concern the loop_video option.
+    /// Progress is measured by counting the frames written so far, out of `total` if it's known. Quick extractions
+    /// (like single images) finish before anything is drawn, and nothing is drawn at all if stderr isn't a terminal.
     ///
     /// # Errors
-    /// Can fail on I/O from `self.display_frame()`
-    fn play_video(&self, delay: Duration, pos: (u16, u16)) -> Result<bool, String> {
-        for frame in &self.frames {
-            self.display_frame(frame)?;
-            std::thread::sleep(delay); // Pause between frames to preserve framerate
+    /// Fails if ffmpeg is still going at `deadline`. See `poll_ffmpeg`.
+    fn wait_with_progress(
+        &self,
+        ffmpeg: &mut Child,
+        total: Option<u64>,
+        deadline: Option<Instant>,
+    ) -> Result<(), Png2tError> {
+        let show = io::stderr().is_terminal();
+        let mut drawn = false;
 
-            if poll(Duration::from_millis(1)).unwrap() {
-                let event = read().unwrap();
-                if [
-                    Event::Key(KeyCode::Char('q').into()),
-                    Event::Key(KeyCode::Esc.into()),
-                    Event::Key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)),
-                ]
-                .contains(&event)
-                {
-                    return Ok(false);
+        let res = loop {
+            thread::sleep(POLL_INTERVAL);
+            match self.poll_ffmpeg(ffmpeg, deadline) {
+                Ok(None) => {}
+                Ok(Some(_)) => break Ok(()),
+                Err(e) => break Err(e),
+            }
+            if !show {
+                continue;
+            }
+
+            let done = self.count_frames();
+            match total {
+                Some(total) if total > 0 => {
+                    let filled = (done.min(total) * PROGRESS_WIDTH / total) as usize;
+                    eprint!(
+                        "\rUnpacking [{}{}] {}/{} frames",
+                        "#".repeat(filled),
+                        " ".repeat(PROGRESS_WIDTH as usize - filled),
+                        done,
+                        total
+                    );
                 }
+                _ => eprint!("\rUnpacking... {} frames", done),
             }
+            drawn = true;
+        };
 
-            // Reset cursor for next frame and overwrite old frame
-            print!("{}", MoveTo(pos.0, pos.1));
+        if drawn {
+            eprint!("\r{}", Clear(ClearType::CurrentLine));
         }
-
-        Ok(self.config.loop_video)
+        res
     }
 
-    /// Creates an audio thread to play sound exactly once.
+    /// Checks whether ffmpeg has finished without waiting on it, killing it if it's still going at `deadline`.
     ///
-    /// Pulls audio from `%self.storage%/audio.mp3` and returns a handle on the audio.
-    fn spawn_audio(&self) -> (OutputStream, OutputStreamHandle) {
-        use rodio::{source::Source, Decoder};
-
-        // Open up audio handles and bind them to avoid deallocation. This line may panic if there is no audio device present.
-        let (_stream, stream_handle) = OutputStream::try_default().unwrap();
+    /// # Returns
+    /// `Ok(Some(status))` once ffmpeg has exited, or `Ok(None)` while it's still working.
+    ///
+    /// # Errors
+    /// Fails if ffmpeg ran past `deadline` and was killed, or if its status can't be checked.
+    fn poll_ffmpeg(
+        &self,
+        ffmpeg: &mut Child,
+        deadline: Option<Instant>,
+    ) -> Result<Option<ExitStatus>, Png2tError> {
+        match ffmpeg.try_wait() {
+            Ok(None) => {}
+            Ok(Some(status)) => return Ok(Some(status)),
+            Err(e) => return Err(Png2tError::io("Unable to check on ffmpeg", e)),
+        }
 
-        // Open up the audio file. Unwraps should never panic unless a race condition is created
-        let source = Decoder::new(BufReader::new(
-            File::open(self.storage.join("audio.mp3")).unwrap(),
-        ));
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            // Wait on it too, so it doesn't hang around as a zombie
+            let _ = ffmpeg.kill();
+            let _ = ffmpeg.wait();
+            return Err(Png2tError::Timeout {
+                file: self.config.file.clone(),
+                timeout: self.config.timeout.unwrap_or_default(),
+            });
+        }
 
-        // Play!
-        stream_handle
-            .play_raw(source.unwrap().convert_samples())
-            .unwrap();
+        Ok(None)
+    }
 
-        // This return ensures these are not deallocated, which would kill the audio thread.
-        (_stream, stream_handle)
+    /// Counts how many frames have been extracted into `self.storage` so far.
+    fn count_frames(&self) -> u64 {
+        fs::read_dir(&self.storage)
+            .map(|entries| {
+                entries
+                    .filter_map(Result::ok)
+                    .filter(|e| {
+                        e.path()
+                            .extension()
+                            .is_some_and(|ext| ext == self.frame_extension())
+                    })
+                    .count() as u64
+            })
+            .unwrap_or(0)
     }
 
-    /// Generate a path to a temporary directory
+    /// Plays frames for `durations` instead of at a constant framerate, if there's exactly one for every frame.
     ///
-    /// Does not create the directory. This mostly exists as an easy location to modify the temporary storage solution later if needed in later versions of this.
-    fn get_tmp_dir() -> PathBuf {
+    /// A mismatch means ffmpeg didn't extract frames the way ffprobe counted them, so the timing can't be trusted.
+    fn use_durations(&mut self, durations: Option<Vec<Duration>>) {
+        if let Some(d) = durations.filter(|d| d.len() == self.frames.len()) {
+            self.delays = d;
+        }
+    }
+
+    /// Empties out `self.storage`, leaving the directory itself in place.
+    ///
+    /// # Errors
+    /// Fails if the directory can't be removed or recreated.
+    fn clear_storage(&self) -> Result<(), Png2tError> {
+        if let Err(e) =
+            fs::remove_dir_all(&self.storage).and_then(|_| fs::create_dir_all(&self.storage))
+        {
+            return Err(Png2tError::io(
+                format!("Unable to clear {}", self.storage.display()),
+                e,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// File extension, and so image format, of the unpacked frames.
+    ///
+    /// PNG is much smaller and faster to decode, but only EXR can hold HDR values.
+    fn frame_extension(&self) -> &'static str {
+        match &self.probe {
+            Ok(Probe { hdr: true, .. }) => "exr",
+            _ => "png",
+        }
+    }
+
+    /// Read from `self.storage` and store every image in there in RGBA8 format into `self.frames`
+    ///
+    /// # Errors
+    /// Can either fail to access the temporary storage directory or individual files, or encounter an invalid PNG.
+    /// Also fails if no frames were extracted at all, such as for audio-only files.
+    /// These issues are unlikely but could be caused by a race condition with another program modifying `self.storage` during execution.
+    fn load_frames(&mut self) -> Result<(), Png2tError> {
+        // Objective: get a list of all files in a directory in human-sorted order
+        let frames: Vec<PathBuf> = fs::read_dir(&self.storage) // gets all files in `&self.storage`
+            .unwrap()
+            .map(|r| String::from(r.unwrap().path().to_str().unwrap())) // Unwrap ReadDir into a DirEntry, which is still not a sortable plain string. Thus, pull the `path()` from it, then cast it to a string, then wrap it in `String::from()` for ownership reasons
+            .sorted_by(|a, b| human_sort::compare(a, b)) // Apply human-sort
+            .map(PathBuf::from) // Cast the list of sorted strings into Path objects instead
+            // Only frames, not the audio, subtitles or cache marker that may be alongside them
+            .filter(|p| p.extension() == Some(OsStr::new(self.frame_extension())))
+            .collect(); // Collect into the final vector
+
+        for (idx, frame) in frames.iter().enumerate() {
+            let reader = image::io::Reader::open(frame);
+            if let Err(e) = reader {
+                return Err(Png2tError::io(
+                    format!(
+                        "Unable to read from temp directory {}",
+                        self.storage.display()
+                    ),
+                    e,
+                ));
+            }
+            let decoder = reader.unwrap().decode();
+            if let Err(e) = decoder {
+                return Err(Png2tError::decode(
+                    if self.frames.len() == 1 {
+                        self.config.file.clone()
+                    } else {
+                        format!(
+                            "frame {}/{} of {}",
+                            idx,
+                            self.frames.len(),
+                            self.config.file
+                        )
+                    },
+                    e,
+                ));
+            }
+
+            // Parse file into RGBA8 format and push it into `self.frames`
+            self.frames
+                .push(tonemap::to_rgba8(decoder.unwrap(), self.config.tonemap));
+        }
+
+        // Audio-only files (and anything else ffmpeg can't pull a picture out of) leave nothing to render
+        if self.frames.is_empty() {
+            return Err(Png2tError::NoVideoStream(self.config.file.clone()));
+        }
+
+        self.is_video = self.frames.len() > 1;
+
+        Ok(())
+    }
+
+    /// Keeps only frames `first` to `last`, dropping the rest. Frames count from 1, as `dump_frames` numbers them,
+    /// and both ends are kept.
+    ///
+    /// # Errors
+    /// Fails if the range is empty or runs past the last frame, or if `self` is a named pipe.
+    pub fn select_frames(&mut self, first: usize, last: usize) -> Result<(), Png2tError> {
+        self.require_unpacked("--frame-range")?;
+        if first == 0 || first > last || last > self.frames.len() {
+            return Err(Png2tError::InvalidArgs(format!(
+                "--frame-range {}:{} doesn't fit in {}, which has {} frame(s) counting from 1",
+                first,
+                last,
+                self.config.file,
+                self.frames.len()
+            )));
+        }
+
+        // Subtitles are timed from the start of the file, so they have to know how much of it was cut off
+        if !self.cues.is_empty() {
+            if let Some(delays) = self.frame_delays()? {
+                self.start += delays[..first - 1].iter().sum::<Duration>();
+            }
+        }
+
+        self.frames.truncate(last);
+        self.frames.drain(..first - 1);
+        // Per-frame timing has to stay lined up with the frames it belongs to
+        if !self.delays.is_empty() {
+            self.delays.truncate(last);
+            self.delays.drain(..first - 1);
+        }
+
+        self.is_video = self.frames.len() > 1;
+        Ok(())
+    }
+
+    /// Transform each frame according to `config`. See `transform::transform_frames`.
+    ///
+    /// # Errors
+    /// Fails if `config` describes an impossible transformation.
+    pub fn transform(&mut self, config: &TransformConfig) -> Result<(), Png2tError> {
+        self.palette = config.palette;
+
+        // Frames from a pipe haven't arrived yet, so the transform is saved for when they do
+        if let Some(live) = &mut self.live {
+            *live = config.clone();
+            return Ok(());
+        }
+
+        transform_frames(&mut self.frames, config)
+    }
+
+    /// Plays the media file in the terminal. Must be initialized with `self.load_frames()` first.
+    ///
+    /// # Errors
+    /// Can error out if `self` contains a video but the FPS cannot be determined.
+    /// Also may fail on I/O or sound device errors.
+    /// Can possibly fail on file I/O, but is only possible by race condition with another program modifying the storage directory.
+    pub fn render(&self) -> Result<(), Png2tError> {
+        self.play().map(|_| ())
+    }
+
+    /// Does the work of `render`, reporting how playback ended so that a playlist knows whether to carry on.
+    fn play(&self) -> Result<Playback, Png2tError> {
+        if let Some(transform) = &self.live {
+            return self.play_live(transform);
+        }
+
+        // Raw mode and cursor moves only make sense on a terminal, so anywhere else just gets the frames one after another
+        if !io::stdout().is_terminal() {
+            return self.print_frames();
+        }
+
+        // Repeated stills are printed like any other text, so the terminal scrolls to fit however many copies there are
+        if !self.is_video && !self.config.interactive && self.config.repeat.is_some() {
+            return self.print_frames();
+        }
+
+        // Work out the video's timing up front so that any warnings print before the terminal is taken over.
+        // Stepping goes at the user's pace, so there's no timing to work out.
+        let timeline = match self.config.step {
+            true => None,
+            false => self.timeline()?,
+        };
+        let mut opts = self.render_options();
+
+        // Create buffer space in the terminal for the image before printing, and save where it starts for quicker
+        // cursor resets when new frames are printed. This also turns off the fancy stuff in the terminal, which I'm
+        // using to later emulate C's `getchar`.
+        // Subtitles go underneath, so need room too
+        let h = self.frames[0].height().div_ceil(2) + self.subtitle_rows();
+        let held = timeline.is_some() || self.is_video || self.config.interactive;
+        let (_screen, pos) = self.make_room(h, opts.column, held)?;
+
+        // The code to play a video is a lot more complex, so it's not worthwhile to try to generalize this for photos vs. videos
+        let mut ended = Playback::Finished;
+        if self.config.step && self.is_video {
+            ended = self.step_frames(pos, &mut opts)?;
+        } else if let Some(timeline) = timeline {
+            // `None` means loop forever, otherwise this counts down the plays left
+            let mut remaining = match self.config.loop_video {
+                true => None,
+                false => Some(self.config.loop_count.unwrap_or(1)),
+            };
+
+            // The output stream has to outlive playback or the audio dies with it, so it's held here for every loop
+            let audio = match self.has_audio && !self.config.mute {
+                true => Some(self.open_audio()?),
+                false => None,
+            };
+
+            // Shrinking frames to fit a resized terminal carries on into later loops, as does hiding subtitles
+            let mut fit = None;
+            let mut captions = true;
+
+            loop {
+                // Start the audio over alongside the video
+                if let Some((_, sink)) = &audio {
+                    self.restart_audio(sink)?;
+                }
+
+                ended = self.play_video(&timeline, 0, pos, &mut opts, &mut fit, &mut captions)?;
+
+                // Stop if the user requested an early exit
+                if ended != Playback::Finished {
+                    break;
+                }
+
+                // Otherwise keep playing until we run out of loops
+                if let Some(n) = &mut remaining {
+                    *n -= 1;
+                    if *n == 0 {
+                        break;
+                    }
+                }
+
+                // The last frame stays up for the pause, which can be cut short like playback can
+                if let Some(stop) = self.config.loop_delay.and_then(pause) {
+                    ended = stop;
+                    break;
+                }
+            }
+        } else if self.config.interactive {
+            ended = self.hold_still(pos, &mut opts)?;
+        } else {
+            // If we just have an image, we simply gotta display it
+            self.display_frame(&self.frames[0], None, &opts)?;
+        }
+
+        // Videos leave the cursor back at the top of the last frame, so move it past the bottom like an image would.
+        // Whatever prints next then starts on a fresh line instead of drawing over the frame.
+        print!("{}", MoveTo(0, pos.1 + h as u16));
+        if let Err(e) = std::io::stdout().flush() {
+            return Err(Png2tError::io("\nFailed to print image", e));
+        }
+
+        Ok(ended)
+    }
+
+    /// Prints every frame once, one after another and without waiting between them, for when stdout isn't a terminal.
+    /// A still image is printed as many times as `self.config.repeat` asks for instead.
+    ///
+    /// Rows end in newlines rather than cursor moves, so the output makes sense in a file or another program.
+    /// On a terminal they also scroll it along, so nothing is drawn over however long the output gets.
+    ///
+    /// # Errors
+    /// Fails on I/O errors while printing, and frames can fail to encode for iTerm2.
+    fn print_frames(&self) -> Result<Playback, Png2tError> {
+        let opts = RenderOptions {
+            newlines: true,
+            ..self.render_options()
+        };
+
+        let copies = match self.is_video {
+            true => 1,
+            false => self.config.repeat.unwrap_or(1) as usize,
+        };
+
+        let mut stdout = io::stdout().lock();
+        for frame in self
+            .frames
+            .iter()
+            .flat_map(|f| itertools::repeat_n(f, copies))
+        {
+            let out = self.draw(frame, None, &opts)?;
+            if let Err(e) = stdout.write_all(out.as_bytes()) {
+                return Err(Png2tError::io("Failed to print image", e));
+            }
+        }
+        if let Err(e) = stdout.flush() {
+            return Err(Png2tError::io("Failed to print image", e));
+        }
+
+        Ok(Playback::Finished)
+    }
+
+    /// Measures how long the first frame takes to render, both on its own and including printing it.
+    ///
+    /// The frame is drawn `BENCH_ITERATIONS` times each way, then the average times are printed.
+    /// Comparing the two shows how much is spent on building the output versus the terminal getting through it.
+    ///
+    /// # Errors
+    /// Fails if stdout isn't a terminal, since printing to one is half of what's measured, or on I/O errors while printing.
+    pub fn bench(&self) -> Result<(), Png2tError> {
+        self.require_unpacked("--bench")?;
+        if !io::stdout().is_terminal() {
+            return Err(Png2tError::InvalidArgs(String::from(
+                "--bench has to be run in a terminal",
+            )));
+        }
+        let opts = self.render_options();
+        let frame = &self.frames[0];
+
+        let start = Instant::now();
+        for _ in 0..BENCH_ITERATIONS {
+            self.draw(frame, None, &opts)?;
+        }
+        let render_time = start.elapsed() / BENCH_ITERATIONS;
+
+        // Same setup as `render`, so every iteration prints over the last
+        let h = frame.height().div_ceil(2);
+        let (screen, pos) = self.make_room(h, opts.column, true)?;
+
+        let start = Instant::now();
+        for _ in 0..BENCH_ITERATIONS {
+            print!("{}", MoveTo(pos.0, pos.1));
+            self.display_frame(frame, None, &opts)?;
+        }
+        let total_time = start.elapsed() / BENCH_ITERATIONS;
+
+        print!("{}", MoveTo(0, pos.1 + h as u16));
+        drop(screen);
+
+        println!(
+            "Rendered a {}x{} frame {} times",
+            frame.width(),
+            frame.height(),
+            BENCH_ITERATIONS
+        );
+        println!(
+            "Render only:     {:.3} ms/frame",
+            render_time.as_secs_f64() * 1000.0
+        );
+        println!(
+            "Render + output: {:.3} ms/frame",
+            total_time.as_secs_f64() * 1000.0
+        );
+
+        Ok(())
+    }
+
+    /// Copies the extracted audio to `path`, as an MP3.
+    ///
+    /// # Errors
+    /// Fails if the file has no audio (or it wasn't extracted), or if it can't be copied.
+    pub fn save_audio(&self, path: &Path) -> Result<(), Png2tError> {
+        if !self.has_audio {
+            return Err(Png2tError::NoAudioStream(self.config.file.clone()));
+        }
+
+        if let Err(e) = fs::copy(self.storage.join("audio.mp3"), path) {
+            return Err(Png2tError::io(
+                format!("Unable to save audio to {}", path.display()),
+                e,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Saves every frame into `dir` as a PNG, exactly as it would be rendered.
+    ///
+    /// Files are numbered from 1 and zero-padded (e.g. `frame001.png`), so they sort in order however they're listed.
+    /// `dir` is created if it doesn't exist, and any frames already in it are overwritten.
+    ///
+    /// # Errors
+    /// Fails if `dir` can't be created or a frame can't be written.
+    pub fn dump_frames(&self, dir: &Path) -> Result<(), Png2tError> {
+        self.require_unpacked("--dump-frames")?;
+        if let Err(e) = fs::create_dir_all(dir) {
+            return Err(Png2tError::io(
+                format!("Unable to create {}", dir.display()),
+                e,
+            ));
+        }
+
+        let digits = self.frames.len().to_string().len();
+        for (idx, frame) in self.frames.iter().enumerate() {
+            // Inversion is normally left until drawing, so it has to be done here
+            let frame = inverted_if(
+                frame,
+                self.config.invert,
+                self.config.linear_invert,
+                self.palette,
+            );
+
+            let path = dir.join(format!("frame{:0digits$}.png", idx + 1));
+            if let Err(e) = frame.save(&path) {
+                return Err(Png2tError::Encode {
+                    context: format!("Unable to save {}", path.display()),
+                    source: e,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Draws `cols` x `rows` evenly spaced frames in a grid, for an overview of the whole video at a glance.
+    ///
+    /// The terminal's width is shared out between the columns, with a space around each, and every frame is shrunk
+    /// to fit its share. With `labels`, how far into the file each frame comes is printed underneath it.
+    /// A video with fewer frames than cells leaves the last cells empty.
+    ///
+    /// With `self.config.interactive`, the grid stays up to be browsed. The arrow keys move a box between frames, and
+    /// Enter plays the video (without sound) from the boxed one until it ends or q or Esc are pressed, then goes back
+    /// to the grid. q or Esc on the grid itself, or Ctrl-C at any point, quit.
+    ///
+    /// # Errors
+    /// Fails if the `--fps` override is invalid, or on I/O errors while printing.
+    pub fn contact_sheet(&self, cols: u32, rows: u32, labels: bool) -> Result<(), Png2tError> {
+        self.require_unpacked("--contact-sheet")?;
+        if !io::stdout().is_terminal() {
+            return Err(Png2tError::InvalidArgs(String::from(
+                "--contact-sheet has to be drawn in a terminal",
+            )));
+        }
+        // Every frame needs at least a column, plus one for the gap next to it, so no more than that can fit across
+        let width = terminal_size().map_or(80, |(cols, _)| cols as u32);
+        let cols = cols.min((width.saturating_sub(1) / 2).max(1));
+        let cells = (cols as usize * rows as usize).min(self.frames.len());
+        if cells == 0 {
+            return Ok(());
+        }
+
+        // When each frame starts, counting from the start of the file rather than from `--seek`
+        let starts = self.source_times();
+        // Worked out now so that any warnings print before the terminal is taken over
+        let timeline = match self.config.interactive {
+            true => self.timeline()?,
+            false => None,
+        };
+        let base = self.render_options();
+
+        // A column and a row are kept free around every frame, for the box around whichever one is picked
+        let cell_width = (width.saturating_sub(1) / cols).saturating_sub(1).max(1);
+        let tiles: Vec<(usize, Image)> = (0..cells)
+            .map(|cell| {
+                let idx = cell * self.frames.len() / cells;
+                let frame = &self.frames[idx];
+                let tile = match fit_within(frame.dimensions(), (cell_width, u32::MAX)) {
+                    Some((w, h)) => resize(frame, w, h, FILTER),
+                    None => frame.clone(),
+                };
+                (idx, tile)
+            })
+            .collect();
+        let tile_rows = tiles
+            .iter()
+            .map(|(_, tile)| tile.height().div_ceil(2))
+            .max()
+            .unwrap_or(0);
+
+        // Playing from a frame shows it at full size, so then there has to be room for that too
+        let sheet_rows = (cells.div_ceil(cols as usize) as u32).saturating_mul(tile_rows + 2);
+        let rows_needed = match timeline {
+            Some(_) => sheet_rows.max(self.frames[0].height().div_ceil(2) + self.subtitle_rows()),
+            None => sheet_rows,
+        };
+        let (screen, (_, top)) = self.make_room(rows_needed, 0, timeline.is_some())?;
+
+        // Where the top left of each frame goes, inside the free column and row around it
+        // Rows too far down for the terminal to address (from sheets of a great many frames) all pile up at the bottom
+        let place = |cell: usize| {
+            let (col, row) = (cell % cols as usize, cell / cols as usize);
+            let x = 1 + col * (cell_width as usize + 1);
+            let y = top as usize + 1 + row * (tile_rows as usize + 2);
+            let clamp = |n: usize| u16::try_from(n).unwrap_or(u16::MAX);
+            (clamp(x), clamp(y))
+        };
+
+        // Draws the box around a frame, or blanks it out, with its label along the bottom
+        let draw_box = |cell: usize, boxed: bool| {
+            let (x, y) = place(cell);
+            let (idx, tile) = &tiles[cell];
+            let (w, h) = (tile.width() as usize, tile.height().div_ceil(2) as u16);
+            let bottom = y.saturating_add(h);
+            let label: String = match labels {
+                true => format_timestamp(starts[*idx]).chars().take(w).collect(),
+                false => String::new(),
+            };
+
+            let (edge, side, corners) = match boxed {
+                true => ('─', '│', ['┌', '┐', '└', '┘']),
+                false => (' ', ' ', [' '; 4]),
+            };
+            // Plain ASCII art is drawn without color, so its box is too
+            if boxed && !self.config.ascii {
+                print!("{}", SetForegroundColor(SELECTION_COLOR));
+            }
+            print!(
+                "{}{}{}{}",
+                MoveTo(x - 1, y - 1),
+                corners[0],
+                edge.to_string().repeat(w),
+                corners[1]
+            );
+            for row in y..bottom {
+                print!(
+                    "{}{}{}{}",
+                    MoveTo(x - 1, row),
+                    side,
+                    MoveTo(x + w as u16, row),
+                    side
+                );
+            }
+            print!(
+                "{}{}{}{}{}{}",
+                MoveTo(x - 1, bottom),
+                corners[2],
+                label,
+                edge.to_string().repeat(w - label.chars().count()),
+                corners[3],
+                ResetColor
+            );
+        };
+
+        let draw_sheet = |boxed: Option<usize>| -> Result<(), Png2tError> {
+            for (cell, (_, tile)) in tiles.iter().enumerate() {
+                let (x, y) = place(cell);
+                let opts = RenderOptions { column: x, ..base };
+                print!("{}", MoveTo(x, y));
+                self.display_frame(tile, None, &opts)?;
+                draw_box(cell, boxed == Some(cell));
+            }
+            if let Err(e) = io::stdout().flush() {
+                return Err(Png2tError::io("\nFailed to print contact sheet", e));
+            }
+            Ok(())
+        };
+
+        match timeline {
+            Some(timeline) => {
+                let mut opts = base;
+                let mut picked = 0;
+                'browse: loop {
+                    print!("{}{}", MoveTo(0, top), Clear(ClearType::FromCursorDown));
+                    draw_sheet(Some(picked))?;
+
+                    loop {
+                        let event = read().unwrap();
+                        if stop_requested(&event).is_some() {
+                            break 'browse;
+                        }
+
+                        if event == Event::Key(KeyCode::Enter.into()) {
+                            print!("{}{}", MoveTo(0, top), Clear(ClearType::FromCursorDown));
+                            let from = timeline
+                                .iter()
+                                .position(|(idx, _)| *idx >= tiles[picked].0)
+                                .unwrap_or(0);
+                            let pos = (opts.column, top);
+                            let ended = self.play_video(
+                                &timeline, from, pos, &mut opts, &mut None, &mut true,
+                            )?;
+                            match ended {
+                                Playback::Quit => break 'browse,
+                                _ => continue 'browse,
+                            }
+                        }
+
+                        let moves = [
+                            (KeyCode::Left, -1),
+                            (KeyCode::Right, 1),
+                            (KeyCode::Up, -(cols as isize)),
+                            (KeyCode::Down, cols as isize),
+                        ];
+                        let Some((_, step)) = moves
+                            .into_iter()
+                            .find(|(code, _)| event == Event::Key((*code).into()))
+                        else {
+                            continue;
+                        };
+                        // Moves off the edge of the grid are ignored
+                        let next = picked as isize + step;
+                        if (0..cells as isize).contains(&next) {
+                            draw_box(picked, false);
+                            picked = next as usize;
+                            draw_box(picked, true);
+                            let _ = io::stdout().flush();
+                        }
+                    }
+                }
+            }
+            None => draw_sheet(None)?,
+        }
+
+        let below = u16::try_from(rows_needed).unwrap_or(u16::MAX);
+        print!("{}", MoveTo(0, top.saturating_add(below)));
+        drop(screen);
+        println!();
+        Ok(())
+    }
+
+    /// Prints a JSON description of every frame instead of drawing it, one line per frame. See `render::render_json`.
+    ///
+    /// Each line also has the `file`, the `frame` number counting from 0, and how long to show it for in `delay_ms`
+    /// (`null` for a still image). Lines are printed as each frame is done, so a reader can start on them straight away.
+    ///
+    /// # Errors
+    /// Fails if the `--fps` override is invalid, or on I/O errors while printing.
+    pub fn print_json(&self) -> Result<(), Png2tError> {
+        self.require_unpacked("--json")?;
+
+        let delays = self.frame_delays()?;
+        // Colors are only described, not drawn, so a terminal without truecolor isn't worth warning about
+        let opts = self.render_options_in(self.detected_color_mode());
+        let mut stdout = io::stdout().lock();
+
+        for (idx, frame) in self.frames.iter().enumerate() {
+            let mut json = render_json(frame, &opts);
+            json["file"] = self.config.file.clone().into();
+            json["frame"] = idx.into();
+            json["delay_ms"] = delays.as_ref().map(|d| d[idx].as_millis() as u64).into();
+
+            if let Err(e) = writeln!(stdout, "{}", json).and_then(|_| stdout.flush()) {
+                return Err(Png2tError::io("Failed to print JSON", e));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Saves the frames to an animated GIF at `path`, drawn as they'd look in the terminal. See `record::record_gif`.
+    ///
+    /// # Errors
+    /// Fails if the `--fps` override is invalid, or if the GIF can't be written.
+    pub fn record(&self, path: &Path) -> Result<(), Png2tError> {
+        self.require_unpacked("--record")?;
+        let delays = self
+            .frame_delays()?
+            .unwrap_or_else(|| vec![Duration::ZERO; self.frames.len()]);
+        record_gif(&self.frames, &delays, &self.render_options(), path)
+    }
+
+    /// Works out how long to hold each frame for, or `None` for a still image.
+    ///
+    /// # Errors
+    /// Fails if the `--fps` override is not a positive number.
+    fn frame_delays(&self) -> Result<Option<Vec<Duration>>, Png2tError> {
+        if !self.is_video {
+            Ok(None)
+        } else if let Some(delay) = self.config.frame_delay {
+            // An explicit delay overrides any timing the file has, without needing to know its framerate at all
+            Ok(Some(vec![delay; self.frames.len()]))
+        } else if self.config.fps.is_none() && !self.delays.is_empty() {
+            // The source knows exactly how long each frame should last
+            Ok(Some(self.delays.clone()))
+        } else {
+            // Based on the fps, calculate how long to wait between each frame printing
+            let delay = Duration::from_millis((1000.0 / self.framerate()?) as u64);
+            Ok(Some(vec![delay; self.frames.len()]))
+        }
+    }
+
+    /// Works out how far into the file each of `self.frames` comes, going by the file's own timing even when `--fps`,
+    /// `--frame-delay` or `--target-fps` change how fast it plays. Subtitles are timed against this.
+    ///
+    /// Files that don't say how long each frame lasts are assumed to hold a steady framerate, of `DEFAULT_FPS` if
+    /// even that can't be found.
+    fn source_times(&self) -> Vec<Duration> {
+        let delays = match self.is_video {
+            false => Vec::new(),
+            true if !self.delays.is_empty() => self.delays.clone(),
+            true => {
+                let fps = self.probe.as_ref().ok().and_then(|p| p.fps);
+                let delay = Duration::from_secs_f32(1.0 / fps.unwrap_or(DEFAULT_FPS));
+                vec![delay; self.frames.len()]
+            }
+        };
+
+        let mut time = self.start;
+        (0..self.frames.len())
+            .map(|idx| {
+                let this = time;
+                time += delays.get(idx).copied().unwrap_or_default();
+                this
+            })
+            .collect()
+    }
+
+    /// Works out which frame to show when, and for how long, or `None` for a still image.
+    ///
+    /// Normally every frame is shown once for its delay. A target framerate instead splits the video into even steps at
+    /// that rate, each showing whichever frame would be up at the time, so long frames are held over several steps.
+    ///
+    /// # Errors
+    /// Fails if the `--fps` or `--target-fps` override is not a positive number.
+    fn timeline(&self) -> Result<Option<Vec<(usize, Duration)>>, Png2tError> {
+        let Some(delays) = self.frame_delays()? else {
+            return Ok(None);
+        };
+        let Some(fps) = self.config.target_fps else {
+            return Ok(Some(delays.into_iter().enumerate().collect()));
+        };
+        if !fps.is_finite() || fps <= 0.0 {
+            return Err(Png2tError::InvalidArgs(format!(
+                "Invalid framerate supplied to --target-fps: {}",
+                fps
+            )));
+        }
+
+        Ok(Some(resample(&delays, Duration::from_secs_f32(1.0 / fps))))
+    }
+
+    /// Gathers up everything `render_image` needs to know, printing any warnings about the terminal along the way.
+    fn render_options(&self) -> RenderOptions {
+        self.render_options_in(self.color_mode())
+    }
+
+    /// Gathers up everything `render_image` needs to know to draw in `color_mode`, without any warnings about it.
+    fn render_options_in(&self, color_mode: ColorMode) -> RenderOptions {
+        RenderOptions {
+            color_mode,
+            background: self.config.background,
+            terminal_background: color::terminal_background(),
+            column: self.start_column(self.frames.first().map_or(0, |f| f.width())),
+            invert: self.config.invert,
+            linear_invert: self.config.linear_invert,
+            palette: self.palette,
+            transparent: self
+                .config
+                .transparent
+                .unwrap_or(RenderOptions::default().transparent),
+            glyph: self.config.glyph,
+            best_fit: self.config.best_fit,
+            newlines: false,
+        }
+    }
+
+    /// Determines the framerate to play a video at.
+    ///
+    /// An explicit `--fps` always wins. Otherwise the framerate reported by `ffprobe` is used,
+    /// falling back to `DEFAULT_FPS` with a warning if it can't be determined.
+    ///
+    /// # Errors
+    /// Fails if the `--fps` override is not a positive number.
+    fn framerate(&self) -> Result<f32, Png2tError> {
+        if let Some(fps) = self.config.fps {
+            if !fps.is_finite() || fps <= 0.0 {
+                return Err(Png2tError::InvalidArgs(format!(
+                    "Invalid framerate supplied to --fps: {}",
+                    fps
+                )));
+            }
+            return Ok(fps);
+        }
+
+        let reason = match &self.probe {
+            Ok(Probe { fps: Some(fps), .. }) => return Ok(*fps),
+            Ok(_) => String::from("no framerate reported"),
+            Err(e) => e.to_string(),
+        };
+
+        eprintln!(
+            "Warning: could not determine framerate of video ({}), falling back to {} fps. Use --fps to override.",
+            reason, DEFAULT_FPS
+        );
+        Ok(DEFAULT_FPS)
+    }
+
+    /// Works out which terminal column frames should be drawn from.
+    ///
+    /// Always the left edge unless centering was asked for. Frames wider than the terminal, or a terminal whose size
+    /// can't be read, also fall back to the left edge.
+    fn start_column(&self, width: u32) -> u16 {
+        if !self.config.center {
+            return 0;
+        }
+
+        match terminal_size() {
+            Some((cols, _)) => (cols as u32).saturating_sub(width) as u16 / 2,
+            None => 0,
+        }
+    }
+
+    /// Picks the color mode to render with.
+    ///
+    /// An explicit `--color-mode` always wins. Otherwise the terminal's support is guessed from the environment,
+    /// with a warning if truecolor doesn't look available since the output will be noticeably less accurate.
+    /// Every file is drawn to the same terminal, so the warning is only printed once however many there are.
+    fn color_mode(&self) -> ColorMode {
+        static WARNED: Once = Once::new();
+
+        let mode = self.detected_color_mode();
+        // iTerm2 images carry their own colors and ASCII has none, so there's nothing to warn about
+        if self.config.color_mode.is_none()
+            && mode != ColorMode::TrueColor
+            && !self.config.iterm
+            && !self.config.ascii
+        {
+            WARNED.call_once(|| {
+                eprintln!("Warning: your terminal does not appear to support truecolor, falling back to 256 colors. Use --color-mode truecolor to override.");
+            });
+        }
+        mode
+    }
+
+    /// Like `color_mode`, but without the warning. The terminal is only looked into once, for every `Media`.
+    fn detected_color_mode(&self) -> ColorMode {
+        static DETECTED: OnceLock<ColorMode> = OnceLock::new();
+
+        match self.config.color_mode {
+            Some(mode) => mode,
+            None => *DETECTED.get_or_init(ColorMode::detect),
+        }
+    }
+
+    /// Turns one image into output for the terminal, with whichever renderer was picked.
+    ///
+    /// If `prev` is already on screen, only what changed since then is drawn. Only the default renderer can do this,
+    /// and the others ignore it.
+    ///
+    /// # Errors
+    /// Frames can fail to encode for iTerm2.
+    fn draw(
+        &self,
+        frame: &Image,
+        prev: Option<&Image>,
+        opts: &RenderOptions,
+    ) -> Result<String, Png2tError> {
+        if self.config.iterm {
+            render_iterm(frame, opts)
+        } else if self.config.ascii {
+            Ok(render_ascii(frame, opts, &self.config.ascii_ramp))
+        } else if let Some(prev) = prev {
+            Ok(render_image_diff(frame, prev, opts))
+        } else {
+            Ok(render_image(frame, opts))
+        }
+    }
+
+    /// Interal function to display one image into the terminal. See `draw` for what `prev` does.
+    ///
+    /// # Errors
+    /// I/O errors can occur when flushing `stdout`, and frames can fail to encode for iTerm2.
+    fn display_frame(
+        &self,
+        frame: &Image,
+        prev: Option<&Image>,
+        opts: &RenderOptions,
+    ) -> Result<(), Png2tError> {
+        print!("{}", self.draw(frame, prev, opts)?);
+
+        if let Err(e) = std::io::stdout().flush() {
+            return Err(Png2tError::io("\nFailed to print image", e));
+        }
+
+        Ok(())
+    }
+
+    /// Plays a video stored in `self.frames`, showing each frame in `timeline` from `from` on for as long as it says
+    ///
+    /// Pressing `i` toggles `opts.invert`, which sticks around for any later loops.
+    /// Pressing `s` saves the frame on screen as a PNG in the current directory.
+    /// Pressing `c` hides or shows subtitles, as `captions` says, which also sticks around for later loops.
+    /// If the terminal is resized so that frames no longer fit, they're shrunk to fit as they're drawn. `fit` holds the
+    /// size they're shrunk to (or `None` if they fit as they are), and likewise carries over between loops.
+    /// Frames near either end of `timeline` are darkened as they're drawn if `self.config.fade` is set.
+    ///
+    /// # Returns
+    /// `Ok(Playback::Finished)` if the video played to the end, or however the user stopped it otherwise.
+    /// This does not concern looping.
+    ///
+    /// # Errors
+    /// Can fail on I/O from `self.display_frame()`
+    fn play_video(
+        &self,
+        timeline: &[(usize, Duration)],
+        from: usize,
+        pos: (u16, u16),
+        opts: &mut RenderOptions,
+        fit: &mut Option<(u32, u32)>,
+        captions: &mut bool,
+    ) -> Result<Playback, Png2tError> {
+        // The last frame drawn and how, so that the next one only has to redraw what changed.
+        // Toggling inversion changes every pixel, so anything drawn with different options has to be redrawn in full.
+        let mut last: Option<(Cow<Image>, RenderOptions)> = None;
+        // When the previous frame started being drawn, and how long it was meant to be shown for
+        let mut shown: Option<(Instant, Duration)> = None;
+        // Where each frame comes in the file, which subtitles are timed against however fast playback goes,
+        // and which subtitle is on screen (`None` before any are drawn)
+        let times = self.source_times();
+        let mut subtitle: Option<Option<usize>> = None;
+        // How far into playback the current frame is, and how long it all takes, for fading
+        let mut elapsed: Duration = timeline[..from].iter().map(|(_, delay)| *delay).sum();
+        let length: Duration = timeline.iter().map(|(_, delay)| *delay).sum();
+
+        for (idx, delay) in &timeline[from..] {
+            let frame = &self.frames[*idx];
+            let mut frame = match *fit {
+                Some((w, h)) => Cow::Owned(resize(frame, w, h, FILTER)),
+                None => Cow::Borrowed(frame),
+            };
+            if let Some(fade) = self.config.fade {
+                let level = fade_level(fade, elapsed, *delay, length);
+                if level < 1.0 {
+                    Lut::identity()
+                        .then(|c| (c as f32 * level).round() as u8)
+                        .apply(frame.to_mut());
+                }
+            }
+
+            let prev = last
+                .as_ref()
+                .filter(|(_, o)| o == opts)
+                .map(|(f, _)| f.as_ref());
+            let start = Instant::now();
+            self.display_frame(&frame, prev, opts)?;
+
+            if !self.cues.is_empty() {
+                let active = active_cue(&self.cues, times[*idx]).filter(|_| *captions);
+                if subtitle != Some(active) {
+                    self.draw_subtitle(active, pos, &frame)?;
+                    subtitle = Some(active);
+                }
+            }
+            elapsed += *delay;
+            last = Some((frame, *opts));
+
+            if self.config.timing {
+                // Raw mode is on, so lines have to be returned to the start by hand
+                let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+                eprint!("frame {}: drawn in {:.2}ms", idx, ms(start.elapsed()));
+                if let Some((prev_start, target)) = shown {
+                    eprint!(
+                        ", {:.2}ms after the last one (target {:.2}ms)",
+                        ms(start - prev_start),
+                        ms(target)
+                    );
+                }
+                eprint!("\r\n");
+            }
+            shown = Some((start, *delay));
+
+            // Pause between frames to preserve framerate, watching for keypresses the whole time so they take effect
+            // right away even when frames are held for a long time
+            let until = Instant::now() + *delay;
+            loop {
+                let left = until.saturating_duration_since(Instant::now());
+                if !poll(left.min(EVENT_POLL_INTERVAL)).unwrap() {
+                    match left.is_zero() {
+                        true => break,
+                        false => continue,
+                    }
+                }
+
+                let event = read().unwrap();
+                if let Some(stop) = stop_requested(&event) {
+                    return Ok(stop);
+                }
+
+                if event == Event::Key(KeyCode::Char('i').into()) {
+                    opts.invert = !opts.invert;
+                }
+
+                if event == Event::Key(KeyCode::Char('s').into()) {
+                    if let Some((frame, _)) = &last {
+                        // The message goes under any subtitles, so it doesn't wipe them out
+                        save_screenshot(
+                            &inverted_if(frame, opts.invert, opts.linear_invert, opts.palette),
+                            pos,
+                            self.subtitle_rows(),
+                        );
+                    }
+                }
+
+                if event == Event::Key(KeyCode::Char('c').into()) && !self.cues.is_empty() {
+                    *captions = !*captions;
+                    if let Some((frame, _)) = &last {
+                        let active = active_cue(&self.cues, times[*idx]).filter(|_| *captions);
+                        self.draw_subtitle(active, pos, frame)?;
+                        subtitle = Some(active);
+                    }
+                }
+
+                #[cfg(unix)]
+                if event == Event::Key(KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL)) {
+                    suspend();
+                }
+
+                if let Event::Resize(cols, rows) = event {
+                    let space = (
+                        cols.saturating_sub(pos.0) as u32,
+                        rows.saturating_sub(pos.1) as u32 * 2,
+                    );
+                    *fit = fit_within(self.frames[0].dimensions(), space);
+
+                    // The terminal may have rewrapped what was on screen, so start over with a clean slate
+                    print!(
+                        "{}{}",
+                        MoveTo(pos.0, pos.1),
+                        Clear(ClearType::FromCursorDown)
+                    );
+                    last = None;
+                    subtitle = None;
+                }
+            }
+
+            // Reset cursor for next frame and overwrite old frame
+            print!("{}", MoveTo(pos.0, pos.1));
+        }
+
+        Ok(Playback::Finished)
+    }
+
+    /// Shows `self.cues[cue]` underneath `frame`, which is on screen at `pos`, centered on it. `None` clears the lines.
+    ///
+    /// Lines too wide for the terminal are cut short, so that they don't wrap into whatever is below.
+    ///
+    /// # Errors
+    /// I/O errors can occur when flushing `stdout`.
+    fn draw_subtitle(
+        &self,
+        cue: Option<usize>,
+        pos: (u16, u16),
+        frame: &Image,
+    ) -> Result<(), Png2tError> {
+        let rows = self.subtitle_rows() as usize;
+        let lines = cue.map_or(&[][..], |cue| &self.cues[cue].lines[..]);
+        let room =
+            terminal_size().map_or(usize::MAX, |(cols, _)| cols.saturating_sub(pos.0) as usize);
+        let top = pos.1 + frame.height().div_ceil(2) as u16;
+
+        for row in 0..rows {
+            let y = top + row as u16;
+            print!("{}{}", MoveTo(pos.0, y), Clear(ClearType::UntilNewLine));
+
+            if let Some(line) = lines.get(row) {
+                let line: String = line.chars().take(room).collect();
+                let pad = (frame.width() as usize).saturating_sub(line.chars().count()) / 2;
+                print!("{}{}", MoveTo(pos.0 + pad as u16, y), line);
+            }
+        }
+
+        if let Err(e) = io::stdout().flush() {
+            return Err(Png2tError::io("\nFailed to print subtitles", e));
+        }
+        Ok(())
+    }
+
+    /// How many lines subtitles take up underneath frames, which is enough for the longest of them.
+    fn subtitle_rows(&self) -> u32 {
+        self.cues
+            .iter()
+            .map(|cue| cue.lines.len())
+            .max()
+            .unwrap_or(0) as u32
+    }
+
+    /// Draws frames from a named pipe as they arrive, transforming each one with `transform` first.
+    ///
+    /// ffmpeg reads the pipe and hands frames over as a stream of uncompressed PAM images, so anything it can read from a
+    /// stream can be written into the pipe. That means a container such as NUT, Matroska or MPEG-TS, or images one after
+    /// another (e.g. `-f image2pipe`). Raw RGBA isn't supported, since nothing in it says how big the frames are.
+    /// Frames are drawn as soon as they're read, so the writer sets the pace. There's no audio.
+    /// They're read on a thread of their own, so keypresses still take effect while the writer is stalled.
+    ///
+    /// Plays until the writer closes the pipe, or the user quits with q, Esc or Ctrl-C. `i` toggles inversion.
+    ///
+    /// # Errors
+    /// Fails if ffmpeg can't be run, sends something that isn't an RGBA frame, or on I/O errors while printing.
+    fn play_live(&self, transform: &TransformConfig) -> Result<Playback, Png2tError> {
+        let mut ffmpeg = match Command::new(&self.config.ffmpeg)
+            .args(["-hide_banner", "-i", &self.config.file])
+            .args(["-f", "image2pipe", "-c:v", "pam", "-pix_fmt", "rgba", "-"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => return Err(Png2tError::io("Unable to run ffmpeg", e)),
+        };
+        let mut reader = BufReader::new(ffmpeg.stdout.take().unwrap());
+
+        // Only one frame is read ahead, so a slow terminal still holds up the writer rather than piling frames up.
+        // The thread finishes once the stream ends, or once killing ffmpeg below cuts it off.
+        let (sender, frames) = sync_channel(1);
+        let file = self.config.file.clone();
+        thread::spawn(move || loop {
+            let frame = read_pam(&mut reader, &file);
+            let done = !matches!(frame, Ok(Some(_)));
+            if sender.send(frame).is_err() || done {
+                break;
+            }
+        });
+
+        let mut transform = transform.clone();
+        let mut opts = self.render_options();
+        // Where the frames are drawn and how many rows they take up, worked out once the first one arrives
+        let mut area: Option<((u16, u16), u32)> = None;
+        let mut screen = None;
+        let mut last: Option<(Image, RenderOptions)> = None;
+
+        let res = 'frames: loop {
+            // Keys are only read once the terminal has been taken over for the first frame. Until then it isn't in raw
+            // mode, so Ctrl-C still works as usual.
+            let next = loop {
+                match frames.recv_timeout(EVENT_POLL_INTERVAL) {
+                    Ok(next) => break next,
+                    Err(RecvTimeoutError::Disconnected) => break Ok(None),
+                    Err(RecvTimeoutError::Timeout) => {}
+                }
+                if screen.is_some() {
+                    if let Some(stop) = self.live_keys(&mut opts) {
+                        break 'frames Ok(stop);
+                    }
+                }
+            };
+            let mut frame = match next {
+                Ok(Some(frame)) => frame,
+                Ok(None) => break Ok(Playback::Finished),
+                Err(e) => break Err(e),
+            };
+            if let Err(e) = transform_frames(std::slice::from_mut(&mut frame), &transform) {
+                break Err(e);
+            }
+            // Every frame is sized the same way, so there's no use logging it again
+            transform.verbose = false;
+            pin_size(&mut transform, frame.dimensions());
+
+            let (pos, _) = match area {
+                Some(area) => area,
+                None => {
+                    // Same setup as `render`, now that there's a frame to size it by
+                    let h = frame.height().div_ceil(2);
+                    opts.column = self.start_column(frame.width());
+                    match self.make_room(h, opts.column, true) {
+                        Ok((s, pos)) => {
+                            screen = Some(s);
+                            *area.insert((pos, h))
+                        }
+                        Err(e) => break Err(e),
+                    }
+                }
+            };
+
+            print!("{}", MoveTo(pos.0, pos.1));
+            let prev = last.as_ref().filter(|(_, o)| *o == opts).map(|(f, _)| f);
+            if let Err(e) = self.display_frame(&frame, prev, &opts) {
+                break Err(e);
+            }
+            last = Some((frame, opts));
+
+            if let Some(stop) = self.live_keys(&mut opts) {
+                break Ok(stop);
+            }
+        };
+
+        // The writer may still be going, but nothing more is going to be drawn
+        let _ = ffmpeg.kill();
+        let _ = ffmpeg.wait();
+
+        if let Some((pos, h)) = area {
+            print!("{}", MoveTo(0, pos.1 + h as u16));
+            let _ = io::stdout().flush();
+        }
+        drop(screen);
+        res
+    }
+
+    /// Takes in every keypress waiting for `play_live`, without holding up the next frame. `i` toggles `opts.invert`.
+    ///
+    /// # Returns
+    /// How the user stopped playback if they did.
+    fn live_keys(&self, opts: &mut RenderOptions) -> Option<Playback> {
+        while poll(Duration::ZERO).unwrap() {
+            let event = read().unwrap();
+            if let Some(stop) = stop_requested(&event) {
+                return Some(stop);
+            }
+            if event == Event::Key(KeyCode::Char('i').into()) {
+                opts.invert = !opts.invert;
+            }
+            #[cfg(unix)]
+            if event == Event::Key(KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL)) {
+                suspend();
+            }
+        }
+        None
+    }
+
+    /// Fails if `self` is a named pipe, which `what` can't be used with since its frames aren't known ahead of time.
+    fn require_unpacked(&self, what: &str) -> Result<(), Png2tError> {
+        match self.live {
+            Some(_) => Err(Png2tError::InvalidArgs(format!(
+                "{} can't be used with a named pipe, since its frames aren't known ahead of time",
+                what
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Calls `make_room` with the scrolling and screen that `self.config` asks for.
+    ///
+    /// `held` says whether what's drawn stays up until playback ends or the user quits. If not, the alternate screen
+    /// is never used, since leaving it as soon as drawing is done would wipe away what was just drawn.
+    fn make_room(
+        &self,
+        rows: u32,
+        column: u16,
+        held: bool,
+    ) -> Result<(Screen, (u16, u16)), Png2tError> {
+        make_room(
+            rows,
+            column,
+            !self.config.no_scroll,
+            self.config.alt_screen && held,
+        )
+    }
+
+    /// Whether `self.config.file` is a named pipe (FIFO) that another program writes into.
+    fn is_fifo(&self) -> bool {
+        is_fifo(&self.config.file)
+    }
+
+    /// Shows one frame of `self.frames` at a time, moving between them as the user presses keys.
+    ///
+    /// Right or space go forward a frame and left goes back, stopping at either end. `i` toggles inversion,
+    /// and q, Esc or Ctrl-C quit.
+    ///
+    /// # Returns
+    /// How the user stopped stepping.
+    ///
+    /// # Errors
+    /// Can fail on I/O from `self.display_frame()`
+    fn step_frames(
+        &self,
+        pos: (u16, u16),
+        opts: &mut RenderOptions,
+    ) -> Result<Playback, Png2tError> {
+        let last = self.frames.len() - 1;
+        let mut idx = 0;
+
+        loop {
+            print!("{}", MoveTo(pos.0, pos.1));
+            self.display_frame(&self.frames[idx], None, opts)?;
+
+            // Windows reports key releases too, which would otherwise step twice per press
+            let Event::Key(KeyEvent {
+                code,
+                modifiers,
+                kind: KeyEventKind::Press,
+                ..
+            }) = read().unwrap()
+            else {
+                continue;
+            };
+
+            match code {
+                KeyCode::Right | KeyCode::Char(' ') => idx = (idx + 1).min(last),
+                KeyCode::Left => idx = idx.saturating_sub(1),
+                KeyCode::Char('i') => opts.invert = !opts.invert,
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(Playback::Skipped),
+                KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(Playback::Quit)
+                }
+                #[cfg(unix)]
+                KeyCode::Char('z') if modifiers.contains(KeyModifiers::CONTROL) => suspend(),
+                _ => {}
+            }
+        }
+    }
+
+    /// Shows a still image until the user quits with q, Esc or Ctrl-C, redrawing it whenever the terminal is resized.
+    ///
+    /// Like a playing video, it's shrunk to fit a smaller terminal, and goes back to its full size once there's room again.
+    /// `i` toggles inversion.
+    ///
+    /// # Returns
+    /// How the user stopped it.
+    ///
+    /// # Errors
+    /// Can fail on I/O from `self.display_frame()`
+    fn hold_still(
+        &self,
+        pos: (u16, u16),
+        opts: &mut RenderOptions,
+    ) -> Result<Playback, Png2tError> {
+        let mut fit = None;
+
+        loop {
+            let frame = match fit {
+                Some((w, h)) => Cow::Owned(resize(&self.frames[0], w, h, FILTER)),
+                None => Cow::Borrowed(&self.frames[0]),
+            };
+            // The terminal may have rewrapped what was on screen, so start over with a clean slate
+            print!(
+                "{}{}",
+                MoveTo(pos.0, pos.1),
+                Clear(ClearType::FromCursorDown)
+            );
+            self.display_frame(&frame, None, opts)?;
+
+            // Nothing changes until a key or resize says so
+            loop {
+                let event = read().unwrap();
+                if let Some(stop) = stop_requested(&event) {
+                    return Ok(stop);
+                }
+
+                if event == Event::Key(KeyCode::Char('i').into()) {
+                    opts.invert = !opts.invert;
+                    break;
+                }
+
+                #[cfg(unix)]
+                if event == Event::Key(KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL)) {
+                    suspend();
+                    break;
+                }
+
+                if let Event::Resize(cols, rows) = event {
+                    let space = (
+                        cols.saturating_sub(pos.0) as u32,
+                        rows.saturating_sub(pos.1) as u32 * 2,
+                    );
+                    fit = fit_within(self.frames[0].dimensions(), space);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Opens the default audio device for playing the extracted audio.
+    ///
+    /// The returned `OutputStream` must be kept alive for as long as the `Sink` should play.
+    ///
+    /// # Errors
+    /// Fails if there is no usable audio device.
+    fn open_audio(&self) -> Result<(OutputStream, Sink), Png2tError> {
+        let (stream, stream_handle) = match OutputStream::try_default() {
+            Ok(s) => s,
+            Err(e) => return Err(Png2tError::Audio(format!("no usable device ({})", e))),
+        };
+
+        match Sink::try_new(&stream_handle) {
+            Ok(sink) => Ok((stream, sink)),
+            Err(e) => Err(Png2tError::Audio(e.to_string())),
+        }
+    }
+
+    /// Plays `%self.storage%/audio.mp3` from the start on `sink`, cutting off anything still playing.
+    ///
+    /// # Errors
+    /// Can fail to open or decode the audio file, but only by a race condition with another program modifying the storage directory.
+    fn restart_audio(&self, sink: &Sink) -> Result<(), Png2tError> {
+        let path = self.storage.join("audio.mp3");
+        let file = match File::open(&path) {
+            Ok(f) => f,
+            Err(e) => {
+                return Err(Png2tError::io(
+                    format!("Unable to open {}", path.display()),
+                    e,
+                ))
+            }
+        };
+        let source = match Decoder::new(BufReader::new(file)) {
+            Ok(s) => s,
+            Err(e) => return Err(Png2tError::decode(path.display().to_string(), e)),
+        };
+
+        // Audio tracks can run longer than the video, so drop whatever is left of the last loop first
+        sink.stop();
+        sink.append(source);
+
+        Ok(())
+    }
+
+    /// Generate a path to a temporary directory
+    ///
+    /// This mostly exists as an easy location to modify the temporary storage solution later if needed in later versions of this.
+    /// Every call gives a different directory, so that several files can be unpacked at once (e.g. for a slideshow).
+    /// It goes in a TEMP directory next to the executable, which isn't created here. If `config.temp_dir` was given it
+    /// goes in there instead, and is created right away, which is what checks it can be written to.
+    ///
+    /// # Errors
+    /// Fails if `config.temp_dir` isn't an existing directory, or can't be written to.
+    fn get_tmp_dir(config: &Config) -> Result<PathBuf, Png2tError> {
+        let name = format!(
+            "{}-{}",
+            process::id(),
+            NEXT_TMP_DIR.fetch_add(1, Ordering::Relaxed)
+        );
+
+        let Some(dir) = &config.temp_dir else {
+            let mut res = std::env::current_exe().unwrap();
+            res.pop();
+            res.push("TEMP");
+            res.push(name);
+            return Ok(res);
+        };
+
+        if !dir.is_dir() {
+            return Err(Png2tError::InvalidArgs(format!(
+                "--temp-dir {} is not an existing directory",
+                dir.display()
+            )));
+        }
+        // Marked as png2t's own, since it sits among whatever else the user keeps there
+        let res = dir.join(format!("png2t-{}", name));
+        if let Err(e) = fs::create_dir(&res) {
+            return Err(Png2tError::io(
+                format!("Unable to write to --temp-dir {}", dir.display()),
+                e,
+            ));
+        }
+        Ok(res)
+    }
+
+    /// Generate a path to the cache directory for `config.file`
+    ///
+    /// Does not create the directory. It's keyed on the file's path, size and modification time so that changing the file invalidates it.
+    /// Transforms are applied after frames are loaded, so they have no effect on what's cached.
+    ///
+    /// # Errors
+    /// Fails if the file can't be found.
+    fn get_cache_dir(config: &Config) -> Result<PathBuf, Png2tError> {
+        let (path, meta) = match fs::canonicalize(&config.file).and_then(|p| {
+            let meta = fs::metadata(&p)?;
+            Ok((p, meta))
+        }) {
+            Ok(res) => res,
+            Err(e) => return Err(Png2tError::io(format!("Unable to read {}", config.file), e)),
+        };
+
+        let mut hasher = DefaultHasher::new();
+        path.hash(&mut hasher);
+        meta.len().hash(&mut hasher);
+        meta.modified().ok().hash(&mut hasher);
+        config.skip_audio.hash(&mut hasher);
+        config.audio_track.hash(&mut hasher);
+        config.video_track.hash(&mut hasher);
+        config.max_frames.hash(&mut hasher);
+        config.seek.hash(&mut hasher);
+        config.embedded_subtitles.hash(&mut hasher);
+
         let mut res = std::env::current_exe().unwrap();
         res.pop();
-        res.push("TEMP");
-        res
+        res.push("CACHE");
+        res.push(format!("{:016x}", hasher.finish()));
+        Ok(res)
     }
 }
 
-impl<'a> Drop for Media<'a> {
+impl Drop for Media {
     fn drop(&mut self) {
+        if self.config.keep_temp {
+            eprintln!("Kept temp directory {}", self.storage.display());
+            return;
+        }
+
+        // The whole point of the cache is to outlive this run
+        if self.config.cache {
+            return;
+        }
+
         if let Err(e) = fs::remove_dir_all(&self.storage) {
             panic!(
                 "Failed to clean temp directory {}: {}",
@@ -416,5 +2406,703 @@ impl<'a> Drop for Media<'a> {
                 e
             );
         }
+
+        // Also tidy up the shared TEMP directory, unless another `Media` (or another run) is still using it.
+        // A `--temp-dir` belongs to the user, so it's left alone even if this was all that was in it.
+        if let (None, Some(parent)) = (&self.config.temp_dir, self.storage.parent()) {
+            let _ = fs::remove_dir(parent);
+        }
+    }
+}
+
+/// Splits frames shown for `delays` into even steps of `step`, pairing each with the frame showing at its start.
+///
+/// Frames longer than a step are held over several of them, and frames shorter than one may be skipped entirely.
+/// The last step is cut short so the total length stays the same.
+fn resample(delays: &[Duration], step: Duration) -> Vec<(usize, Duration)> {
+    let total: Duration = delays.iter().sum();
+    let mut timeline = Vec::new();
+
+    // When the current frame (`idx`) stops showing
+    let mut idx = 0;
+    let mut frame_end = delays.first().copied().unwrap_or_default();
+
+    let mut t = Duration::ZERO;
+    while t < total && !step.is_zero() {
+        while t >= frame_end && idx + 1 < delays.len() {
+            idx += 1;
+            frame_end += delays[idx];
+        }
+
+        timeline.push((idx, step.min(total - t)));
+        t += step;
+    }
+
+    timeline
+}
+
+/// Gets the terminal ready to draw `rows` lines from the cursor down, and returns where the first of them starts.
+///
+/// Frames start on the cursor's line, or the next one if something has already been printed on it. If `scroll` is set
+/// and there isn't room below for them (plus a line after, for the cursor to be left on), the terminal is scrolled up
+/// just far enough to make it. Nothing is scrolled into the scrollback if they already fit.
+/// With `alternate`, frames go on the alternate screen instead, starting from the top.
+///
+/// The terminal is held in raw mode, with the cursor in `column` of the first line, until the returned `Screen` is dropped.
+///
+/// # Errors
+/// Fails if there's no terminal to take over, such as when output is going to a file, or it doesn't say where the
+/// cursor is.
+fn make_room(
+    rows: u32,
+    column: u16,
+    scroll: bool,
+    alternate: bool,
+) -> Result<(Screen, (u16, u16)), Png2tError> {
+    if let Err(e) = enable_raw_mode() {
+        return Err(Png2tError::io("Unable to take over the terminal", e));
+    }
+    let screen = Screen { alternate };
+
+    // The alternate screen starts out empty, so there's nothing to make room around
+    if alternate {
+        let _ = execute!(io::stdout(), EnterAlternateScreen, MoveTo(column, 0));
+        return Ok((screen, (column, 0)));
+    }
+
+    let cursor = || match position() {
+        Ok(pos) => Ok(pos),
+        Err(e) => Err(Png2tError::io(
+            "Unable to find the cursor in the terminal",
+            e,
+        )),
+    };
+    let (x, _) = cursor()?;
+    if x > 0 {
+        print!("\r\n");
+        let _ = io::stdout().flush();
+    }
+    let (_, mut top) = cursor()?;
+
+    if let (true, Some((_, height))) = (scroll, terminal_size()) {
+        let below = height.saturating_sub(top) as u32;
+        let needed = (rows + 1).saturating_sub(below);
+        if needed > 0 {
+            // Line feeds on the bottom line push everything up by one
+            print!("{}{}", MoveTo(0, height - 1), "\n".repeat(needed as usize));
+            top = top.saturating_sub(needed as u16);
+        }
+    }
+
+    print!("{}", MoveTo(column, top));
+    Ok((screen, (column, top)))
+}
+
+/// How bright to draw a frame shown for `delay` from `at` into a video `length` long, fading in and out over `fade`.
+///
+/// Goes from 0 (black) to 1 (untouched), judged from the middle of when the frame is shown, so neither the first nor
+/// the last frame is lost to black entirely.
+fn fade_level(fade: Duration, at: Duration, delay: Duration, length: Duration) -> f32 {
+    if fade.is_zero() {
+        return 1.0;
+    }
+
+    let middle = at + delay / 2;
+    let nearest_end = middle.min(length.saturating_sub(middle));
+    (nearest_end.as_secs_f32() / fade.as_secs_f32()).min(1.0)
+}
+
+/// Formats `time` as minutes and seconds to a tenth (e.g. `01:02.5`), with hours in front only if it needs them.
+fn format_timestamp(time: Duration) -> String {
+    let tenths = time.as_millis() / 100;
+    let (hours, minutes, seconds) = (tenths / 36000, tenths / 600 % 60, tenths % 600);
+    match hours {
+        0 => format!("{:02}:{:02}.{}", minutes, seconds / 10, seconds % 10),
+        _ => format!(
+            "{}:{:02}:{:02}.{}",
+            hours,
+            minutes,
+            seconds / 10,
+            seconds % 10
+        ),
+    }
+}
+
+/// Saves `frame` as a PNG in the current directory, named after the current time, then says where underneath it.
+///
+/// `frame` is assumed to be on screen at `pos`, followed by `skip` lines that are in use (such as for subtitles), then
+/// a free line, which is where the message goes. Failing to save is reported the same way rather than stopping playback.
+fn save_screenshot(frame: &Image, pos: (u16, u16), skip: u32) {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis());
+    let path = format!("png2t-{}.png", millis);
+
+    let message = match frame.save(&path) {
+        Ok(()) => format!("Saved frame to {}", path),
+        Err(e) => format!("Unable to save frame: {}", e),
+    };
+    print!(
+        "{}{}{}{}",
+        MoveTo(0, pos.1 + (frame.height().div_ceil(2) + skip) as u16),
+        Clear(ClearType::CurrentLine),
+        message,
+        MoveTo(pos.0, pos.1)
+    );
+    let _ = io::stdout().flush();
+}
+
+/// Keeps the terminal set up for drawing frames, as done by `make_room`, and puts it back the way it was when dropped.
+///
+/// Being a guard means the terminal is restored however drawing ends, including when it bails out with an error.
+struct Screen {
+    /// Whether the alternate screen was entered, and so has to be left.
+    alternate: bool,
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        if self.alternate {
+            let _ = execute!(io::stdout(), LeaveAlternateScreen);
+        }
+        let _ = disable_raw_mode();
+    }
+}
+
+/// How playback of a file came to an end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Playback {
+    /// It played all the way through.
+    Finished,
+    /// The user pressed q or Esc, which only stops the current file.
+    Skipped,
+    /// The user pressed Ctrl-C, which stops everything.
+    Quit,
+}
+
+/// Waits for `delay`, watching for the keys that stop playback the whole time.
+///
+/// # Returns
+/// How the user stopped playback if they did, or `None` once `delay` has passed.
+fn pause(delay: Duration) -> Option<Playback> {
+    let until = Instant::now() + delay;
+    loop {
+        let left = until.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return None;
+        }
+        if !poll(left.min(EVENT_POLL_INTERVAL)).unwrap() {
+            continue;
+        }
+
+        let event = read().unwrap();
+        if let Some(stop) = stop_requested(&event) {
+            return Some(stop);
+        }
+
+        #[cfg(unix)]
+        if event == Event::Key(KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL)) {
+            suspend();
+        }
+    }
+}
+
+/// Whether `event` is one of the keys that stop playback: q, Esc or Ctrl-C.
+fn stop_requested(event: &Event) -> Option<Playback> {
+    if *event == Event::Key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)) {
+        return Some(Playback::Quit);
+    }
+
+    [
+        Event::Key(KeyCode::Char('q').into()),
+        Event::Key(KeyCode::Esc.into()),
+    ]
+    .contains(event)
+    .then_some(Playback::Skipped)
+}
+
+/// Fixes `transform` to keep making frames `dims` in size, as the first frame from a pipe came out, so that later ones
+/// stay inside the terminal without repeating the warning about clamping them to `max_width`.
+///
+/// Does nothing if `transform` has no `max_width`, since then nothing could have been shrunk to begin with.
+fn pin_size(transform: &mut TransformConfig, dims: (u32, u32)) {
+    if transform.max_width.take().is_none() {
+        return;
+    }
+
+    // Mirroring doubles whatever size is asked for, so it's pinned to a single tile
+    let (w, h) = dims;
+    transform.size = match transform.mirror {
+        true => Some(((w / 2).max(1), (h / 2).max(1))),
+        false => Some((w, h)),
+    };
+    // Only the flipping is left of any scaling, since the size it came to is now set directly
+    transform.scale = transform.scale.map(|(x, y)| (x.signum(), y.signum()));
+    // That size includes any padding out to a square, which fitting inside of recreates
+    if transform.square {
+        transform.fit = Fit::Contain;
+    }
+}
+
+/// Whether the ffmpeg at `ffmpeg` is 5.1 or later, and so has `-fps_mode` in place of `-vsync`.
+///
+/// Builds whose version can't be made out (such as ones straight from git) are assumed to be recent.
+fn supports_fps_mode(ffmpeg: &Path) -> bool {
+    let version = Command::new(ffmpeg)
+        .arg("-version")
+        .stderr(Stdio::null())
+        .output();
+    let Ok(output) = version else {
+        return true;
+    };
+
+    let banner = String::from_utf8_lossy(&output.stdout);
+    match banner.lines().next().and_then(parse_ffmpeg_version) {
+        Some(version) => version >= (5, 1),
+        None => true,
+    }
+}
+
+/// Picks the major and minor version out of the first line of `ffmpeg -version`, e.g. `ffmpeg version 4.4.2-0ubuntu1`.
+/// Some distributions put an `n` in front of the number.
+fn parse_ffmpeg_version(line: &str) -> Option<(u32, u32)> {
+    let version = line.strip_prefix("ffmpeg version ")?;
+    let version = version.strip_prefix('n').unwrap_or(version);
+    let mut parts = version.split(|c: char| !c.is_ascii_digit());
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next().and_then(|p| p.parse().ok()).unwrap_or(0);
+    Some((major, minor))
+}
+
+/// Whether `file` is a named pipe. Always false outside of Unix.
+fn is_fifo(file: &str) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        fs::metadata(file).is_ok_and(|m| m.file_type().is_fifo())
+    }
+    #[cfg(not(unix))]
+    {
+        false
+    }
+}
+
+/// The format of `file`, if it looks like a still image that the `image` crate can decode by itself.
+///
+/// Formats are recognized by their magic bytes, falling back to the extension. GIFs and animated PNGs are left out,
+/// since only their first frame would be read. Named pipes are never read from here, as that would use up their contents.
+fn still_format(file: &str) -> Option<ImageFormat> {
+    if is_fifo(file) {
+        return None;
+    }
+
+    let format = ImageReader::open(file)
+        .and_then(|r| r.with_guessed_format())
+        .ok()?
+        .format()?;
+    match format {
+        ImageFormat::Gif => None,
+        ImageFormat::Png if is_apng(file) => None,
+        f if f.reading_enabled() => Some(f),
+        _ => None,
+    }
+}
+
+/// Whether unpacking `file` has to go through ffmpeg and ffprobe, rather than just decoding it directly.
+///
+/// This is only a guess made from the start of the file, so a file that looks like an image but fails to decode
+/// will still end up needing ffmpeg.
+pub fn needs_ffmpeg(file: &str) -> bool {
+    still_format(file).is_none()
+}
+
+/// Whether the PNG at `file` is animated. Anything that can't be read is treated as a plain PNG.
+fn is_apng(file: &str) -> bool {
+    File::open(file)
+        .map_err(ImageError::IoError)
+        .and_then(|f| PngDecoder::new(BufReader::new(f)))
+        .is_ok_and(|d| d.is_apng())
+}
+
+/// Reads the next frame from a stream of PAM images, as written by ffmpeg's `pam` encoder with the `rgba` pixel format.
+///
+/// # Returns
+/// `Ok(None)` once the stream has ended.
+///
+/// # Errors
+/// Fails if the stream can't be read, or holds something other than RGBA images. `file` is where the stream comes from.
+fn read_pam(reader: &mut impl BufRead, file: &str) -> Result<Option<Image>, Png2tError> {
+    let (mut width, mut height) = (None, None);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) => return Ok(None),
+            Ok(_) => {}
+            Err(e) => return Err(Png2tError::io(format!("Unable to read from {}", file), e)),
+        }
+
+        let mut fields = line.split_whitespace();
+        match (fields.next(), fields.next()) {
+            (Some("WIDTH"), Some(w)) => width = w.parse().ok(),
+            (Some("HEIGHT"), Some(h)) => height = h.parse().ok(),
+            (Some("DEPTH"), Some(d)) if d != "4" => {
+                return Err(Png2tError::decode(file, "ffmpeg didn't send RGBA frames"))
+            }
+            (Some("ENDHDR"), _) => break,
+            _ => {}
+        }
+    }
+
+    let (Some(width), Some(height)) = (width, height) else {
+        return Err(Png2tError::decode(
+            file,
+            "a frame is missing its dimensions",
+        ));
+    };
+
+    let mut data = vec![0; width as usize * height as usize * 4];
+    if let Err(e) = reader.read_exact(&mut data) {
+        return Err(Png2tError::io(format!("Unable to read from {}", file), e));
+    }
+
+    // The buffer is exactly the right size, so this is always `Some`
+    Ok(Image::from_raw(width, height, data))
+}
+
+/// What to do once a slide has been shown.
+enum SlideStep {
+    Next,
+    Previous,
+    Quit,
+}
+
+/// Shows each of `slides` in turn as a slideshow. Every slide must have been unpacked first.
+///
+/// Right, space or enter move on to the next slide and left goes back to the previous one, while q, Esc or Ctrl-C quit early.
+/// With a `delay`, slides also move on by themselves once it has passed. Videos play through once, without audio,
+/// and then wait on their last frame like any other slide.
+/// If stdout isn't a terminal, each slide is printed in turn, as `render` would print it, without waiting in between.
+///
+/// # Errors
+/// Fails if a video has an invalid `--fps` override, or on I/O errors while printing.
+pub fn play_slideshow(slides: &[Media], delay: Option<Duration>) -> Result<(), Png2tError> {
+    // Without a terminal there's nothing to step through, so every slide is printed one after another instead
+    if !io::stdout().is_terminal() {
+        for slide in slides {
+            slide.require_unpacked("A slideshow")?;
+            slide.print_frames()?;
+        }
+        return Ok(());
+    }
+
+    // Work out everything that might print a warning before the terminal is taken over
+    let mut prepared = Vec::with_capacity(slides.len());
+    for slide in slides {
+        slide.require_unpacked("A slideshow")?;
+        prepared.push((slide.timeline()?, slide.render_options()));
+    }
+
+    // Make room for the tallest slide and its subtitles, so that moving between them never scrolls the terminal
+    let h = slides
+        .iter()
+        .map(|s| s.frames[0].height().div_ceil(2) + s.subtitle_rows())
+        .max()
+        .unwrap_or(0);
+    let scroll = !slides.iter().any(|s| s.config.no_scroll);
+    let alternate = slides.iter().any(|s| s.config.alt_screen);
+    let (_screen, (_, top)) = make_room(h, 0, scroll, alternate)?;
+
+    let mut idx = 0;
+    while let (Some(slide), Some((timeline, opts))) = (slides.get(idx), prepared.get_mut(idx)) {
+        // The previous slide may have been bigger than this one, so wipe it out completely
+        print!(
+            "{}{}{}",
+            MoveTo(0, top),
+            Clear(ClearType::FromCursorDown),
+            MoveToColumn(opts.column)
+        );
+
+        let ended = match timeline {
+            Some(timeline) => {
+                slide.play_video(timeline, 0, (opts.column, top), opts, &mut None, &mut true)?
+            }
+            None => {
+                slide.display_frame(&slide.frames[0], None, opts)?;
+                Playback::Finished
+            }
+        };
+        if ended != Playback::Finished {
+            break;
+        }
+
+        match wait_for_slide_step(delay) {
+            SlideStep::Next => idx += 1,
+            SlideStep::Previous => idx = idx.saturating_sub(1),
+            SlideStep::Quit => break,
+        }
+    }
+
+    // Leave the cursor underneath the slideshow for whatever prints next
+    print!("{}", MoveTo(0, top + h as u16));
+    Ok(())
+}
+
+/// Plays each of `files` back-to-back as a playlist, loading each one with `load` just before it plays.
+///
+/// Only one file is held at a time: each is dropped, along with its temporary storage and audio, before the next is loaded.
+/// q or Esc skip ahead to the next file, while Ctrl-C stops the whole playlist.
+///
+/// # Errors
+/// Fails as soon as a file fails to load or play, without going on to the rest.
+pub fn play_playlist(
+    files: &[String],
+    mut load: impl FnMut(&str) -> Result<Media, Png2tError>,
+) -> Result<(), Png2tError> {
+    for file in files {
+        let media = load(file)?;
+        if media.play()? == Playback::Quit {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Blocks until the user picks where to go from the current slide, or until `delay` runs out.
+fn wait_for_slide_step(delay: Option<Duration>) -> SlideStep {
+    let deadline = delay.map(|d| Instant::now() + d);
+
+    loop {
+        let event = match deadline {
+            Some(deadline) => {
+                if !poll(deadline.saturating_duration_since(Instant::now())).unwrap() {
+                    return SlideStep::Next;
+                }
+                read().unwrap()
+            }
+            None => read().unwrap(),
+        };
+
+        // Windows reports key releases too, which shouldn't count as a second press
+        if let Event::Key(KeyEvent {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press,
+            ..
+        }) = event
+        {
+            match code {
+                KeyCode::Right | KeyCode::Char(' ') | KeyCode::Enter => return SlideStep::Next,
+                KeyCode::Left => return SlideStep::Previous,
+                KeyCode::Char('q') | KeyCode::Esc => return SlideStep::Quit,
+                KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => {
+                    return SlideStep::Quit
+                }
+                #[cfg(unix)]
+                KeyCode::Char('z') if modifiers.contains(KeyModifiers::CONTROL) => suspend(),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pam_frames_are_read_one_at_a_time() {
+        let mut stream = Vec::new();
+        for v in [10, 20] {
+            stream.extend_from_slice(
+                b"P7\nWIDTH 2\nHEIGHT 1\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n",
+            );
+            stream.extend_from_slice(&[v; 8]);
+        }
+        let mut reader = &stream[..];
+
+        let first = read_pam(&mut reader, "pipe").unwrap().unwrap();
+        assert_eq!(first.dimensions(), (2, 1));
+        assert_eq!(first.get_pixel(1, 0), &Rgba([10; 4]));
+        assert_eq!(
+            read_pam(&mut reader, "pipe")
+                .unwrap()
+                .unwrap()
+                .get_pixel(0, 0),
+            &Rgba([20; 4])
+        );
+        assert!(read_pam(&mut reader, "pipe").unwrap().is_none());
+    }
+
+    #[test]
+    fn only_stills_skip_ffmpeg() {
+        let dir = std::env::temp_dir();
+        let png = dir.join(format!("png2t-needs-{}.png", process::id()));
+        let gif = dir.join(format!("png2t-needs-{}.gif", process::id()));
+        Image::new(1, 1).save(&png).unwrap();
+        Image::new(1, 1).save(&gif).unwrap();
+
+        let (png_needs, gif_needs) = (
+            needs_ffmpeg(&png.to_string_lossy()),
+            needs_ffmpeg(&gif.to_string_lossy()),
+        );
+        fs::remove_file(&png).unwrap();
+        fs::remove_file(&gif).unwrap();
+
+        assert!(!png_needs);
+        assert!(gif_needs);
+        assert!(needs_ffmpeg("/nonexistent/video.mp4"));
+    }
+
+    #[test]
+    fn stills_load_without_ffmpeg() {
+        let file = std::env::temp_dir().join(format!("png2t-still-{}.png", process::id()));
+        Image::from_pixel(3, 2, Rgba([1, 2, 3, 255]))
+            .save(&file)
+            .unwrap();
+
+        let mut media = MediaBuilder::new(file.to_string_lossy())
+            .ffmpeg("/nonexistent/ffmpeg")
+            .ffprobe("/nonexistent/ffprobe")
+            .build()
+            .unwrap();
+        let loaded = media.unpack_file();
+        fs::remove_file(&file).unwrap();
+
+        loaded.unwrap();
+        assert_eq!(media.frames.len(), 1);
+        assert_eq!(media.frames[0].get_pixel(2, 1), &Rgba([1, 2, 3, 255]));
+        assert!(!media.is_video);
+    }
+
+    #[test]
+    fn frame_ranges_keep_timing_aligned() {
+        let mut media = MediaBuilder::new("frames.gif").build().unwrap();
+        media.frames = (0..5)
+            .map(|v| Image::from_pixel(1, 1, Rgba([v; 4])))
+            .collect();
+        media.delays = (0..5).map(Duration::from_secs).collect();
+
+        assert!(media.select_frames(0, 2).is_err());
+        assert!(media.select_frames(3, 6).is_err());
+
+        media.select_frames(2, 3).unwrap();
+        assert_eq!(media.frames[0].get_pixel(0, 0), &Rgba([1; 4]));
+        assert_eq!(media.frames.len(), 2);
+        assert_eq!(
+            media.delays,
+            [Duration::from_secs(1), Duration::from_secs(2)]
+        );
+        assert!(media.is_video);
+    }
+
+    #[test]
+    fn frames_reload_from_a_finished_cache() {
+        let mut media = MediaBuilder::new("frames.gif").build().unwrap();
+        for (n, v) in [(2, 20), (10, 100)] {
+            Image::from_pixel(1, 1, Rgba([v; 4]))
+                .save(media.storage.join(format!("frame{}.png", n)))
+                .unwrap();
+        }
+        fs::write(media.storage.join(CACHE_MARKER), "").unwrap();
+        fs::write(media.storage.join("audio.mp3"), "").unwrap();
+
+        media.load_frames().unwrap();
+        assert_eq!(media.frames.len(), 2);
+        assert_eq!(media.frames[1].get_pixel(0, 0), &Rgba([100; 4]));
+    }
+
+    #[test]
+    fn short_ascii_ramps_are_rejected() {
+        for ramp in [vec![], vec!['#']] {
+            let media = MediaBuilder::new("frames.gif")
+                .ascii_ramp(Some(ramp))
+                .build();
+            assert!(matches!(media, Err(Png2tError::InvalidArgs(_))));
+        }
+        assert!(MediaBuilder::new("frames.gif")
+            .ascii_ramp(Some(vec![' ', '#']))
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn source_times_ignore_playback_speed() {
+        let mut media = MediaBuilder::new("frames.gif")
+            .fps(Some(100.0))
+            .build()
+            .unwrap();
+        media.frames = vec![Image::new(1, 1); 3];
+        media.delays = vec![Duration::from_millis(500); 3];
+        media.is_video = true;
+        media.start = Duration::from_secs(10);
+
+        let ms = Duration::from_millis;
+        assert_eq!(media.source_times(), [ms(10_000), ms(10_500), ms(11_000)]);
+        assert_eq!(media.timeline().unwrap().unwrap()[1], (1, ms(10)));
+    }
+
+    #[test]
+    fn pinned_sizes_stay_the_same_for_later_frames() {
+        for (mirror, square) in [(false, false), (true, false), (false, true), (true, true)] {
+            let mut transform = TransformConfig {
+                max_width: Some(40),
+                mirror,
+                square,
+                ..TransformConfig::default()
+            };
+            let mut first = vec![Image::new(90, 30)];
+            transform_frames(&mut first, &transform).unwrap();
+            pin_size(&mut transform, first[0].dimensions());
+
+            let mut later = vec![Image::new(90, 30)];
+            transform_frames(&mut later, &transform).unwrap();
+            assert_eq!(later[0].dimensions(), first[0].dimensions());
+        }
+    }
+
+    #[test]
+    fn ffmpeg_versions_are_read_from_the_banner() {
+        let version = |v| parse_ffmpeg_version(&format!("ffmpeg version {} Copyright (c)", v));
+        assert_eq!(version("4.4.2-0ubuntu0.22.04.1"), Some((4, 4)));
+        assert_eq!(version("n6.1.1"), Some((6, 1)));
+        assert_eq!(version("5.1"), Some((5, 1)));
+        assert_eq!(version("7"), Some((7, 0)));
+        assert_eq!(version("N-112345-gabcdef"), None);
+        assert_eq!(parse_ffmpeg_version("something else"), None);
+    }
+
+    #[test]
+    fn timestamps_only_show_hours_when_needed() {
+        assert_eq!(format_timestamp(Duration::ZERO), "00:00.0");
+        assert_eq!(format_timestamp(Duration::from_millis(62_549)), "01:02.5");
+        assert_eq!(format_timestamp(Duration::from_secs(3723)), "1:02:03.0");
+    }
+
+    #[test]
+    fn fades_ramp_up_and_down_at_the_ends() {
+        let ms = Duration::from_millis;
+        let level = |at| fade_level(ms(400), ms(at), ms(100), ms(2000));
+
+        assert_eq!(level(0), 0.125);
+        assert_eq!(level(300), 0.875);
+        assert_eq!(level(1000), 1.0);
+        assert_eq!(level(1900), 0.125);
+        assert_eq!(fade_level(ms(0), ms(0), ms(100), ms(2000)), 1.0);
+    }
+
+    #[test]
+    fn resample_holds_slow_frames() {
+        let ms = Duration::from_millis;
+        let timeline = resample(&[ms(500), ms(250)], ms(200));
+        assert_eq!(
+            timeline,
+            [(0, ms(200)), (0, ms(200)), (0, ms(200)), (1, ms(150))]
+        );
+
+        // Frames that fall between steps are skipped
+        let timeline = resample(&[ms(100), ms(50), ms(50), ms(100)], ms(100));
+        assert_eq!(timeline, [(0, ms(100)), (1, ms(100)), (3, ms(100))]);
     }
 }
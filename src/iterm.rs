@@ -0,0 +1,84 @@
+use image::{codecs::png::PngEncoder, ColorType, ImageEncoder};
+
+use crate::{
+    error::Png2tError,
+    render::{inverted_if, RenderOptions},
+    Image,
+};
+
+/// Characters used by standard base64, indexed by 6-bit value.
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Renders an image as an iTerm2 inline image, using its `OSC 1337` file transfer protocol.
+///
+/// The frame is sent as a PNG and stretched over the same number of cells the half-block renderer would use,
+/// so it takes up the same space while drawing at the terminal's full resolution.
+/// Of `opts`, only `invert` (along with `linear_invert` and `palette`) applies. Transparency is left for the terminal to deal with.
+///
+/// # Errors
+/// Fails if the frame can't be encoded as a PNG.
+pub fn render_iterm(img: &Image, opts: &RenderOptions) -> Result<String, Png2tError> {
+    // The frame is sent as-is, so inverting has to be done on a copy
+    let img = inverted_if(img, opts.invert, opts.linear_invert, opts.palette);
+
+    let mut png = Vec::new();
+    if let Err(e) = PngEncoder::new(&mut png).write_image(
+        img.as_raw(),
+        img.width(),
+        img.height(),
+        ColorType::Rgba8,
+    ) {
+        return Err(Png2tError::Encode {
+            context: String::from("Unable to encode frame as PNG"),
+            source: e,
+        });
+    }
+
+    Ok(format!(
+        "\x1b]1337;File=inline=1;size={};width={};height={};preserveAspectRatio=0:{}\x07",
+        png.len(),
+        img.width(),
+        img.height().div_ceil(2),
+        base64(&png)
+    ))
+}
+
+/// Encodes bytes as padded, standard base64.
+fn base64(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        // Pack up to 3 bytes into the top of a 24-bit group, then read it back out 6 bits at a time
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, &b)| acc | (b as u32) << (16 - 8 * i));
+
+        for i in 0..4 {
+            // A chunk of n bytes fills n + 1 characters, and the rest are padding
+            match i <= chunk.len() {
+                true => out.push(BASE64_ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char),
+                false => out.push('='),
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_matches_rfc_4648_vectors() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+        assert_eq!(base64(b"fooba"), "Zm9vYmE=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+}
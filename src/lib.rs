@@ -0,0 +1,42 @@
+//! Render any media file supported by FFMPEG into your terminal!
+//!
+//! For a single image, `render_image` turns an `Image` into a string ready to print, or `render_iterm` for iTerm2's inline images.
+//! Whole media files (including videos with sound) are handled by `Media`, built with `MediaBuilder`.
+//! Frames can be resized and recolored beforehand with `transform_frames` and a `TransformConfig`.
+//! Anything that can fail returns a `Png2tError`, which can be matched on to tell what went wrong.
+
+mod color;
+mod error;
+mod helpers;
+mod iterm;
+mod lut;
+mod palette;
+mod probe;
+mod record;
+mod render;
+mod subtitles;
+#[cfg(unix)]
+mod suspend;
+mod tonemap;
+mod transform;
+mod tty;
+
+pub use crate::{
+    color::{parse_color, ColorMode},
+    error::Png2tError,
+    helpers::{needs_ffmpeg, play_playlist, play_slideshow, Image, Media, MediaBuilder},
+    iterm::render_iterm,
+    palette::Palette,
+    probe::Probe,
+    record::record_gif,
+    render::{
+        parse_ascii_ramp, parse_transparent, render_ascii, render_image, render_image_diff,
+        render_json, Glyph, RenderOptions, ASCII_RAMP,
+    },
+    tonemap::Tonemap,
+    transform::{parse_scale, parse_size, transform_frames, Fit, Threshold, TransformConfig},
+    tty::terminal_size,
+};
+
+#[cfg(unix)]
+pub use crate::{suspend::handle_suspend, tty::use_tty};
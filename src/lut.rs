@@ -0,0 +1,102 @@
+use std::sync::OnceLock;
+
+use crate::{
+    tonemap::{decode_srgb, encode_srgb},
+    Image,
+};
+
+/// A mapping from every value a color channel can take to a new one, worked out ahead of time.
+///
+/// Point operations (ones that change each channel on its own, like inverting or posterizing) are composed into a
+/// single table, so however many are applied, each channel of each pixel only takes one lookup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Lut([u8; 256]);
+
+impl Lut {
+    /// The table that leaves every value as it is.
+    pub(crate) fn identity() -> Self {
+        Lut(std::array::from_fn(|c| c as u8))
+    }
+
+    /// Gives a table that does whatever `self` does, then `op` to the result.
+    pub(crate) fn then(self, op: impl Fn(u8) -> u8) -> Self {
+        Lut(self.0.map(op))
+    }
+
+    /// What `self` maps `c` to.
+    pub(crate) fn get(&self, c: u8) -> u8 {
+        self.0[c as usize]
+    }
+
+    /// Whether applying `self` would change nothing, in which case it can be skipped.
+    pub(crate) fn is_identity(&self) -> bool {
+        *self == Self::identity()
+    }
+
+    /// Maps the color channels of every pixel in `frame` through `self`, leaving alpha alone.
+    pub(crate) fn apply(&self, frame: &mut Image) {
+        for pixel in frame.chunks_exact_mut(4) {
+            for c in &mut pixel[..3] {
+                *c = self.0[*c as usize];
+            }
+        }
+    }
+}
+
+/// Inverts one color channel.
+pub(crate) fn invert(c: u8) -> u8 {
+    u8::MAX - c
+}
+
+/// Inverts one color channel in linear light, by decoding it from sRGB, inverting that, then encoding it again.
+///
+/// Black and white still swap, but the amount of light is inverted rather than how bright it looks,
+/// so midtones come out much lighter than with `invert`.
+pub(crate) fn invert_linear(c: u8) -> u8 {
+    encode_srgb(1.0 - decode_srgb(c))
+}
+
+/// A table that inverts channels, in linear light if `linear` is set. See `invert` and `invert_linear`.
+///
+/// Each table is only worked out the first time it's needed.
+pub(crate) fn inversion(linear: bool) -> &'static Lut {
+    static GAMMA: OnceLock<Lut> = OnceLock::new();
+    static LINEAR: OnceLock<Lut> = OnceLock::new();
+
+    match linear {
+        true => LINEAR.get_or_init(|| Lut::identity().then(invert_linear)),
+        false => GAMMA.get_or_init(|| Lut::identity().then(invert)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    #[test]
+    fn composed_table_matches_applying_each_op() {
+        let halve = |c: u8| c / 2;
+        let lut = Lut::identity().then(invert).then(halve);
+        for c in 0..=255u8 {
+            assert_eq!(lut.0[c as usize], halve(invert(c)));
+        }
+
+        assert!(Lut::identity().is_identity());
+        assert!(Lut::identity().then(invert).then(invert).is_identity());
+        assert!(!lut.is_identity());
+
+        let mut frame = Image::from_pixel(1, 1, Rgba([255, 100, 0, 128]));
+        lut.apply(&mut frame);
+        assert_eq!(frame.get_pixel(0, 0), &Rgba([0, 77, 127, 128]));
+    }
+
+    #[test]
+    fn linear_inversion_swaps_ends_and_lightens_midtones() {
+        let lut = inversion(true);
+        assert_eq!((lut.get(0), lut.get(255)), (255, 0));
+        assert!(lut.get(128) > inversion(false).get(128));
+        assert!((0..=255).all(|c| lut.get(c) == invert_linear(c)));
+    }
+}
@@ -1,55 +1,365 @@
-use std::process::{Command, Stdio};
+use std::{
+    env, io,
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
+    time::Duration,
+};
 
 use clap::Parser;
 
-mod helpers;
-use crate::helpers::*;
+use png2t::{
+    needs_ffmpeg, parse_ascii_ramp, parse_color, parse_scale, parse_size, parse_transparent,
+    play_playlist, play_slideshow, terminal_size, ColorMode, Fit, Glyph, Media, MediaBuilder,
+    Palette, Png2tError, Probe, Threshold, Tonemap, TransformConfig,
+};
 
 #[derive(Parser, Debug)]
 #[command(
     name = "png2t",
     author = "imani@bepri.dev",
     version = "0.1.2",
-    about = "A command-line tool to render a PNG to the terminal."
+    about = "A command-line tool to render a PNG to the terminal.",
+    after_help = "Exit codes: 1 for general failures, 2 if ffmpeg or ffprobe is missing (or the arguments are invalid), 3 if a file doesn't exist, 4 if a file can't be decoded"
 )]
 pub struct Args {
-    #[arg(help = "Path to a media file to render.", name = "FILE")]
-    file: String,
+    #[arg(help = "Path to a media file to render. Several files are shown as a slideshow.", name = "FILE", required = true)]
+    files: Vec<String>,
 
-    #[arg(help = "Invert all color", long)]
+    #[arg(help = "Invert all color. Press i to toggle while a video plays", long)]
     invert: bool,
 
+    #[arg(help = "Invert in linear light instead of on the sRGB values, which lightens midtones. Applies whenever colors are inverted", long)]
+    linear_invert: bool,
+
     #[arg(help = "Flip image horizontally", long)]
     flip_h: bool,
 
     #[arg(help = "Flip image vertically", long)]
     flip_v: bool,
 
-    #[arg(help = "Dimensions to adjust to, in the format NxN", long)]
-    size: Option<String>,
+    #[arg(help = "Dimensions to adjust to, in the format NxN", long, value_parser = parse_size)]
+    size: Option<(u32, u32)>,
+
+    #[arg(
+        help = "How --size deals with a different aspect ratio: contain (letterbox), cover (crop) or stretch [default: stretch]",
+        long,
+        requires = "size"
+    )]
+    fit: Option<Fit>,
+
+    #[arg(help = "How to bring down the highlights of HDR videos and images (such as EXR): reinhard (keeps every detail) or aces (filmic, more contrast) [default: reinhard]", long)]
+    tonemap: Option<Tonemap>,
+
+    #[arg(help = "Pad frames out to a square, centering them in it. The padding is transparent, or --background", long)]
+    square: bool,
+
+    #[arg(help = "Tile frames 2x2 with mirrored copies of themselves, for a kaleidoscope effect", long)]
+    mirror: bool,
+
+    #[arg(help = "Resize frames without SIMD, for older CPUs (only matters if built with the fast-resize feature)", long)]
+    no_simd: bool,
+
+    #[arg(
+        help = "Most threads to transform frames on at once [default: one per CPU]",
+        long,
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    threads: Option<u32>,
+
+    #[arg(
+        help = "Make the image exactly this many columns wide, keeping its aspect ratio",
+        long,
+        value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with_all = ["size", "scale", "scale_x", "scale_y", "preserve_dims"]
+    )]
+    cols: Option<u32>,
+
+    #[arg(
+        help = "Factor to scale by, or XxY for separate width and height factors. A negative factor also flips that way, so -1 turns the image upside down",
+        long,
+        value_parser = parse_scale,
+        allow_hyphen_values = true
+    )]
+    scale: Option<(f32, f32)>,
 
-    #[arg(help = "Factor to scale by", long)]
-    scale: Option<f32>,
+    #[arg(help = "Factor to scale the width by. A negative factor also flips the image horizontally", long, allow_negative_numbers = true, conflicts_with = "scale")]
+    scale_x: Option<f32>,
+
+    #[arg(help = "Factor to scale the height by. A negative factor also flips the image vertically", long, allow_negative_numbers = true, conflicts_with = "scale")]
+    scale_y: Option<f32>,
 
     #[arg(help = "Avoid automatically resizing the image", long)]
     preserve_dims: bool,
 
+    #[arg(
+        help = "Height of a terminal cell divided by its width, to correct stretched images. Ignored with --size [default: 2.0]",
+        long
+    )]
+    cell_aspect: Option<f32>,
+
+    #[arg(help = "Snap colors to a fixed palette: gameboy, cga or grayscale4", long)]
+    palette: Option<Palette>,
+
+    #[arg(
+        help = "Average out blocks of this many pixels across, for a mosaic look",
+        long,
+        value_name = "BLOCK",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pixelate: Option<u32>,
+
+    #[arg(help = "Draw only the edges in the image, like a sketch", long)]
+    edges: bool,
+
+    #[arg(help = "Rotate the hue of every color by this many degrees", long, value_name = "DEGREES", allow_hyphen_values = true)]
+    hue: Option<f32>,
+
+    #[arg(help = "Multiply the saturation of every color by this factor, e.g. 0.5 for muted colors or 2 for vivid ones", long, value_name = "FACTOR")]
+    saturation: Option<f32>,
+
+    #[arg(help = "Draw in shades of gray. The same as --saturation 0", long, conflicts_with = "saturation")]
+    grayscale: bool,
+
+    #[arg(
+        help = "Reduce every color channel to this many levels, for a poster-like look",
+        long,
+        value_name = "LEVELS",
+        value_parser = clap::value_parser!(u16).range(2..=256)
+    )]
+    posterize: Option<u16>,
+
+    #[arg(help = "Draw only in black and white, turning colors at least this bright (0-255) white", long, value_name = "LEVEL")]
+    threshold: Option<u8>,
+
+    #[arg(help = "Like --threshold, but pick the level for each frame automatically", long, conflicts_with = "threshold")]
+    threshold_auto: bool,
+
+    #[arg(help = "Sharpen the image after resizing, with an unsharp mask of this radius (e.g. 1.0)", long, value_name = "AMOUNT")]
+    sharpen: Option<f32>,
+
+    #[arg(help = "Blur the image after resizing, with a gaussian of this sigma (e.g. 1.0)", long, value_name = "SIGMA")]
+    blur: Option<f32>,
+
+    #[arg(
+        help = "Colors to render with: truecolor, 256 or 16 [default: detected from the terminal]",
+        long
+    )]
+    color_mode: Option<ColorMode>,
+
+    #[arg(help = "Color to fill transparent areas with, in the format RRGGBB", long, value_parser = parse_color)]
+    background: Option<[u8; 3]>,
+
+    #[arg(help = "Draw fully transparent areas with this character instead of leaving them blank. Give two to alternate between them in a checkerboard", long, value_name = "CHARS", value_parser = parse_transparent)]
+    transparent_as: Option<[char; 2]>,
+
+    #[arg(help = "Half-block character to draw with: lower (▄) or upper (▀). Some fonts line one up better than the other [default: lower]", long)]
+    glyph: Option<Glyph>,
+
+    #[arg(help = "Pick each character and its colors to best match the picture, including quarter-cell edges between characters. Slower to draw", long, conflicts_with_all = ["iterm", "ascii", "ascii_ramp"])]
+    best_fit: bool,
+
+    #[arg(help = "Center the image horizontally in the terminal", long)]
+    center: bool,
+
+    #[arg(help = "Draw from the cursor without scrolling the terminal to make room first", long)]
+    no_scroll: bool,
+
+    #[arg(help = "Draw on the terminal's alternate screen, like less or vim, so whatever was there before comes back once playback ends. Stills only use it with --interactive", long)]
+    alt_screen: bool,
+
+    #[cfg(unix)]
+    #[arg(help = "Draw on this terminal device (e.g. /dev/pts/3) instead of the current one, and read keys from it", long, value_name = "DEVICE")]
+    tty: Option<PathBuf>,
+
+    #[arg(help = "Draw with iTerm2's inline images, for full resolution on terminals that support them", long)]
+    iterm: bool,
+
+    #[arg(help = "Draw plain ASCII art without any color. Also enabled by setting NO_COLOR", long)]
+    ascii: bool,
+
+    #[arg(help = "Characters to draw ASCII art with, from darkest to brightest, or a preset: classic or blocks. Implies --ascii", long, value_name = "CHARS", value_parser = parse_ascii_ramp)]
+    // Spelled out in full so clap takes it as one value, rather than collecting a `char` per occurrence
+    ascii_ramp: Option<::std::vec::Vec<char>>,
+
+    #[arg(help = "Framerate to play videos at, overriding the detected one", long)]
+    fps: Option<f32>,
+
+    #[arg(
+        help = "Redraw videos at this framerate, holding frames of slower ones so they stay responsive to keypresses",
+        long,
+        value_name = "FPS"
+    )]
+    target_fps: Option<f32>,
+
+    #[arg(
+        help = "Milliseconds to hold each frame of a video for, ignoring its framerate",
+        long,
+        value_name = "MS",
+        conflicts_with = "fps"
+    )]
+    frame_delay: Option<u64>,
+
     #[arg(long, id = "loop")]
     loop_video: bool,
 
+    #[arg(
+        help = "Play a video this many times, then exit",
+        long,
+        value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with = "loop"
+    )]
+    loop_count: Option<u32>,
+
+    #[arg(help = "Milliseconds to pause on the last frame between loops of a video", long, value_name = "MS")]
+    loop_delay: Option<u64>,
+
+    #[arg(help = "Milliseconds to fade videos in from black at the start, and out to black at the end", long, value_name = "MS")]
+    fade: Option<u64>,
+
+    #[arg(help = "Step through a video a frame at a time with the arrow keys instead of playing it", long)]
+    step: bool,
+
+    #[arg(help = "Keep an image on screen until q is pressed, redrawing it to fit if the terminal is resized", long)]
+    interactive: bool,
+
+    #[arg(help = "Print how long each frame of a video takes to draw, and how far apart frames really are, to stderr. Best redirected to a file", long)]
+    timing: bool,
+
+    #[arg(
+        help = "Only unpack this many frames from the start of a video, for a quick preview",
+        long,
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    max_frames: Option<u32>,
+
+    #[arg(
+        help = "Print a still image this many times, one under another. Videos ignore it",
+        long,
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    repeat: Option<u32>,
+
+    #[arg(
+        help = "Show only the first frame of a video, like a thumbnail",
+        long,
+        conflicts_with = "max_frames"
+    )]
+    once: bool,
+
+    #[arg(
+        help = "Start this far into a video, as seconds or [HH:]MM:SS. With --once, shows just the frame at that point",
+        long,
+        value_name = "TIME",
+        value_parser = parse_timestamp
+    )]
+    seek: Option<Duration>,
+
+    #[arg(
+        help = "Only play frames FIRST to LAST, counting from 1 as --dump-frames does",
+        long,
+        value_name = "FIRST:LAST",
+        value_parser = parse_frame_range
+    )]
+    frame_range: Option<(usize, usize)>,
+
+    #[arg(help = "Which audio track to play, counting from 0 [default: chosen by ffmpeg]", long)]
+    audio_track: Option<usize>,
+
+    #[arg(help = "Which video track to render, counting from 0, for files that also hold thumbnails and the like [default: chosen by ffmpeg]", long)]
+    video_track: Option<usize>,
+
+    #[arg(help = "Show subtitles from this SubRip file under the video as it plays", long, value_name = "SRT")]
+    subtitles: Option<PathBuf>,
+
+    #[arg(help = "Show the file's own subtitles under the video as it plays, if it has any", long, conflicts_with = "subtitles")]
+    embedded_subtitles: bool,
+
     #[arg(help = "Mute audio if any is present", long)]
     mute: bool,
+
+    #[arg(help = "Don't extract audio at all, which also mutes it", long)]
+    skip_audio: bool,
+
+    #[arg(help = "Cache extracted frames to speed up future runs on the same file", long)]
+    cache: bool,
+
+    #[arg(help = "Don't delete the extracted frames and audio when done, and print where they were left", long)]
+    keep_temp: bool,
+
+    #[arg(help = "Extract frames and audio into a new subdirectory of this directory, such as a RAM disk, instead of next to the executable", long, value_name = "DIR", conflicts_with = "cache")]
+    temp_dir: Option<PathBuf>,
+
+    #[arg(help = "Seconds to show each slide for before moving on, when given several files [default: wait for a keypress]", long, value_parser = parse_seconds)]
+    slide_delay: Option<Duration>,
+
+    #[arg(help = "Play several files back-to-back, one after another, instead of as a slideshow. q skips to the next file and Ctrl-C stops them all", long, conflicts_with_all = ["slide_delay", "json", "bench"])]
+    playlist: bool,
+
+    #[arg(help = "Also save what gets rendered to a GIF at this path", long, value_name = "GIF")]
+    record: Option<PathBuf>,
+
+    #[arg(help = "Also save the file's audio as an MP3 at this path", long, value_name = "MP3", conflicts_with = "skip_audio")]
+    extract_audio: Option<PathBuf>,
+
+    #[arg(help = "Save the frames as PNGs into this directory, after transforming them, instead of rendering", long, value_name = "DIR")]
+    dump_frames: Option<PathBuf>,
+
+    #[arg(help = "Draw this many evenly spaced frames side by side in a grid instead of playing; with --interactive, pick one with the arrow keys and Enter to play from it", long, value_name = "COLSxROWS", value_parser = parse_size, conflicts_with_all = ["json", "bench", "playlist"])]
+    contact_sheet: Option<(u32, u32)>,
+
+    #[arg(help = "Label each frame of --contact-sheet with how far into the file it comes", long, requires = "contact_sheet")]
+    sheet_labels: bool,
+
+    #[arg(help = "Print each frame's characters and colors as a line of JSON instead of drawing them", long)]
+    json: bool,
+
+    #[arg(help = "Time how long the first frame takes to render and print, then exit", long)]
+    bench: bool,
+
+    #[arg(help = "Give up if unpacking takes longer than this many seconds", long, value_parser = parse_seconds)]
+    timeout: Option<Duration>,
+
+    #[arg(help = "Path to the ffmpeg binary", long, env = "PNG2T_FFMPEG", default_value = "ffmpeg")]
+    ffmpeg_path: PathBuf,
+
+    #[arg(help = "Path to the ffprobe binary", long, env = "PNG2T_FFPROBE", default_value = "ffprobe")]
+    ffprobe_path: PathBuf,
+
+    #[arg(help = "Print information about the file and exit without rendering", long)]
+    dry_run: bool,
+
+    #[arg(help = "Log how the output dimensions were chosen to stderr", long)]
+    verbose: bool,
 }
 
-fn main() -> Result<(), String> {
-    if let Err(e) = Command::new("ffmpeg").stdout(Stdio::null()).stderr(Stdio::null()).spawn() {
-        if let std::io::ErrorKind::NotFound = e.kind() {
-            return Err(String::from(
-                "Could not find ffmpeg! Please install first or ensure it is on your PATH.",
-            ));
-        }
+fn main() {
+    if let Err(e) = run(Args::parse()) {
+        eprintln!("Error: {}", e);
+        process::exit(e.code());
+    }
+}
+
+fn run(args: Args) -> Result<(), Png2tError> {
+    // Still images are decoded directly, so ffmpeg and ffprobe are only needed for everything else
+    if args.files.iter().any(|file| needs_ffmpeg(file)) {
+        // ffmpeg and ffprobe usually come together, but not always
+        check_installed(&args.ffmpeg_path, "ffmpeg", "--ffmpeg-path")?;
+        check_installed(&args.ffprobe_path, "ffprobe", "--ffprobe-path")?;
     }
 
+    // URLs (http://, rtsp:// and so on) are left for ffmpeg to open, so only local paths can be checked up front
+    if let Some(file) = args.files.iter().find(|file| !file.contains("://") && !Path::new(file).exists()) {
+        return Err(Png2tError::FileNotFound(file.clone()));
+    }
+
+    #[cfg(unix)]
+    if let Some(tty) = &args.tty {
+        png2t::use_tty(tty)?;
+    }
+
+    #[cfg(unix)]
+    png2t::handle_suspend()?;
+
     #[cfg(target_os="windows")]
     {
         println!("Warning: This program is capable of running on Windows, but it faces a lot of difficulties due to default Windows behavior.");
@@ -61,25 +371,244 @@ fn main() -> Result<(), String> {
             let handle = match GetStdHandle(STD_HANDLE(11)) {
                 Ok(h) => h,
                 Err(e) => {
-                    return Err(format!("Could not communicate with output device: {e}"));
+                    return Err(Png2tError::io("Could not communicate with output device", io::Error::other(e.to_string())));
                 },
             };
             if let Err(e) = SetConsoleMode(handle, CONSOLE_MODE(0x0001)) {
-                return Err(format!("Could not enable ANSI escapes: {e}"));
+                return Err(Png2tError::io("Could not enable ANSI escapes", io::Error::other(e.to_string())));
+            }
+        }
+    }
+
+    if args.dry_run {
+        // Probing always goes through ffprobe, even for stills that would otherwise be decoded directly
+        check_installed(&args.ffprobe_path, "ffprobe", "--ffprobe-path")?;
+
+        for (i, file) in args.files.iter().enumerate() {
+            if args.files.len() > 1 {
+                // Blank line between files, then a header so it's clear which is which
+                if i > 0 {
+                    println!();
+                }
+                println!("{}:", file);
+            }
+            println!("{}", Probe::with_video_track(&args.ffprobe_path, file, args.video_track.unwrap_or(0))?);
+        }
+        return Ok(());
+    }
+
+    if args.files.len() > 1 {
+        if args.record.is_some() {
+            return Err(Png2tError::InvalidArgs(String::from("--record only works with a single file")));
+        }
+        if args.extract_audio.is_some() {
+            return Err(Png2tError::InvalidArgs(String::from("--extract-audio only works with a single file")));
+        }
+        if args.dump_frames.is_some() {
+            return Err(Png2tError::InvalidArgs(String::from("--dump-frames only works with a single file")));
+        }
+        if args.contact_sheet.is_some() {
+            return Err(Png2tError::InvalidArgs(String::from("--contact-sheet only works with a single file")));
+        }
+    }
+
+    // Each file is only loaded once it's its turn to play, so only one is ever unpacked at a time
+    if args.playlist {
+        return play_playlist(&args.files, |file| load(file, &args));
+    }
+
+    let mut slides = Vec::with_capacity(args.files.len());
+    for file in &args.files {
+        slides.push(load(file, &args)?);
+    }
+
+    if args.json {
+        for media in &slides {
+            media.print_json()?;
+        }
+        return Ok(());
+    }
+
+    if args.bench {
+        for media in &slides {
+            media.bench()?;
+        }
+        return Ok(());
+    }
+
+    match slides.as_slice() {
+        [media] => {
+            if let Some(path) = &args.extract_audio {
+                media.save_audio(path)?;
             }
+            if let Some(dir) = &args.dump_frames {
+                return media.dump_frames(dir);
+            }
+            if let Some((cols, rows)) = args.contact_sheet {
+                return media.contact_sheet(cols, rows, args.sheet_labels);
+            }
+            if let Some(path) = &args.record {
+                media.record(path)?;
+            }
+            media.render()?
         }
+        slides => play_slideshow(slides, args.slide_delay)?,
     }
 
-    let args = Args::parse();
+    Ok(())
+}
 
-    let mut media = match Media::new(&args) {
-        Err(e) => return Err(format!("Couldn't load file {}: {}", args.file, e)),
-        Ok(m) => m,
+/// Loads and transforms `file` according to `args`, ready to be rendered.
+fn load(file: &str, args: &Args) -> Result<Media, Png2tError> {
+    let transform = TransformConfig {
+        flip_h: args.flip_h,
+        flip_v: args.flip_v,
+        size: args.size,
+        fit: args.fit.unwrap_or_default(),
+        cols: args.cols,
+        scale: match (args.scale, args.scale_x, args.scale_y) {
+            (None, None, None) => None,
+            (Some(scale), _, _) => Some(scale),
+            (None, x, y) => Some((x.unwrap_or(1.0), y.unwrap_or(1.0))),
+        },
+        preserve_dims: args.preserve_dims,
+        cell_aspect: args.cell_aspect,
+        palette: args.palette,
+        pixelate: args.pixelate,
+        edges: args.edges,
+        hue: args.hue,
+        saturation: if args.grayscale { Some(0.0) } else { args.saturation },
+        posterize: args.posterize,
+        threshold: match args.threshold_auto {
+            true => Some(Threshold::Auto),
+            false => args.threshold.map(Threshold::Level),
+        },
+        sharpen: args.sharpen,
+        blur: args.blur,
+        mirror: args.mirror,
+        square: args.square,
+        // Only frames drawn in the terminal have to fit in it
+        max_width: match args.json || args.dump_frames.is_some() {
+            true => None,
+            false => terminal_size().map(|(cols, _)| cols as u32),
+        },
+        simd: !args.no_simd,
+        threads: args.threads.map(|n| n as usize),
+        verbose: args.verbose,
     };
 
+    let mut media = MediaBuilder::new(file)
+        .invert(args.invert)
+        .linear_invert(args.linear_invert)
+        .color_mode(args.color_mode)
+        .background(args.background)
+        .transparent(args.transparent_as)
+        .glyph(args.glyph.unwrap_or_default())
+        .best_fit(args.best_fit)
+        .fps(args.fps)
+        .target_fps(args.target_fps)
+        .frame_delay(args.frame_delay.map(Duration::from_millis))
+        .loop_video(args.loop_video)
+        .loop_count(args.loop_count)
+        .loop_delay(args.loop_delay.map(Duration::from_millis))
+        .fade(args.fade.map(Duration::from_millis))
+        .step(args.step)
+        .interactive(args.interactive)
+        .timing(args.timing)
+        .mute(args.mute)
+        // A single frame is rendered as a still, so there's no use for more of them or for audio (unless it's being saved)
+        .skip_audio(args.skip_audio || (args.once && args.extract_audio.is_none()))
+        .max_frames(if args.once { Some(1) } else { args.max_frames })
+        .repeat(args.repeat)
+        .seek(args.seek)
+        .audio_track(args.audio_track)
+        .video_track(args.video_track)
+        .tonemap(args.tonemap.unwrap_or_default())
+        .subtitles(args.subtitles.clone())
+        .embedded_subtitles(args.embedded_subtitles)
+        .cache(args.cache)
+        .keep_temp(args.keep_temp)
+        .temp_dir(args.temp_dir.clone())
+        .center(args.center)
+        .no_scroll(args.no_scroll)
+        .alt_screen(args.alt_screen)
+        .timeout(args.timeout)
+        .ffmpeg(&args.ffmpeg_path)
+        .ffprobe(&args.ffprobe_path)
+        .iterm(args.iterm)
+        .ascii(args.ascii || args.ascii_ramp.is_some() || no_color())
+        .ascii_ramp(args.ascii_ramp.clone())
+        .build()?;
+
     media.unpack_file()?;
-    media.transform()?;
-    media.render()?;
+    if let Some((first, last)) = args.frame_range {
+        media.select_frames(first, last)?;
+    }
+    media.transform(&transform)?;
 
-    Ok(())
+    Ok(media)
+}
+
+/// Makes sure `binary` can be run, returning a friendly error about installing `name` (or pointing `flag` at it) if not.
+fn check_installed(binary: &Path, name: &'static str, flag: &'static str) -> Result<(), Png2tError> {
+    let status = Command::new(binary)
+        .arg("-version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+
+    match status {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Err(Png2tError::FfmpegNotFound {
+            name,
+            path: binary.to_path_buf(),
+            flag,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Whether the user has asked for no color via the `NO_COLOR` convention (https://no-color.org), i.e. it's set and not empty.
+fn no_color() -> bool {
+    env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty())
+}
+
+/// Parses a point in time as a number of seconds (`90.5`) or in the format `[HH:]MM:SS` (`01:30.5`), like ffmpeg does.
+fn parse_timestamp(s: &str) -> Result<Duration, String> {
+    let err = || format!("'{}' is not a time in seconds or [HH:]MM:SS", s);
+
+    // Later fields count for 60 times less than the one before, with the seconds last
+    let mut secs = 0.0;
+    let fields: Vec<&str> = s.trim().split(':').collect();
+    if fields.len() > 3 {
+        return Err(err());
+    }
+    for (i, field) in fields.iter().enumerate() {
+        let value: f64 = field.parse().map_err(|_| err())?;
+        if !value.is_finite() || value < 0.0 || (i > 0 && value >= 60.0) {
+            return Err(err());
+        }
+        secs = secs * 60.0 + value;
+    }
+
+    Duration::try_from_secs_f64(secs).map_err(|_| err())
+}
+
+/// Parses a range of frames in the format `FIRST:LAST`, such as `100:200`.
+fn parse_frame_range(s: &str) -> Result<(usize, usize), String> {
+    let err = || format!("'{}' is not a range of frames in the format FIRST:LAST", s);
+
+    let (first, last) = s.split_once(':').ok_or_else(err)?;
+    match (first.trim().parse(), last.trim().parse()) {
+        (Ok(first), Ok(last)) if first >= 1 && first < last => Ok((first, last)),
+        (Ok(_), Ok(_)) => Err(format!("'{}' must start from at least 1 and end after it starts", s)),
+        _ => Err(err()),
+    }
+}
+
+/// Parses a positive number of seconds, such as `2.5`.
+fn parse_seconds(s: &str) -> Result<Duration, String> {
+    match s.trim().parse::<f32>().map(Duration::try_from_secs_f32) {
+        Ok(Ok(d)) if !d.is_zero() => Ok(d),
+        _ => Err(format!("'{}' is not a positive number of seconds", s)),
+    }
 }
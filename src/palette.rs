@@ -0,0 +1,90 @@
+use std::str::FromStr;
+
+use crate::color::distance;
+
+/// A small, fixed set of colors to snap every pixel to, for a retro look.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Palette {
+    /// The four shades of green of the original Game Boy screen.
+    GameBoy,
+    /// The 16 colors of IBM's Color Graphics Adapter.
+    Cga,
+    /// Black, white and two evenly spaced grays.
+    Grayscale4,
+}
+
+const GAMEBOY: [[u8; 3]; 4] = [[15, 56, 15], [48, 98, 48], [139, 172, 15], [155, 188, 15]];
+
+const CGA: [[u8; 3]; 16] = [
+    [0x00, 0x00, 0x00],
+    [0x00, 0x00, 0xaa],
+    [0x00, 0xaa, 0x00],
+    [0x00, 0xaa, 0xaa],
+    [0xaa, 0x00, 0x00],
+    [0xaa, 0x00, 0xaa],
+    [0xaa, 0x55, 0x00],
+    [0xaa, 0xaa, 0xaa],
+    [0x55, 0x55, 0x55],
+    [0x55, 0x55, 0xff],
+    [0x55, 0xff, 0x55],
+    [0x55, 0xff, 0xff],
+    [0xff, 0x55, 0x55],
+    [0xff, 0x55, 0xff],
+    [0xff, 0xff, 0x55],
+    [0xff, 0xff, 0xff],
+];
+
+const GRAYSCALE4: [[u8; 3]; 4] = [[0; 3], [85; 3], [170; 3], [255; 3]];
+
+impl Palette {
+    /// Every color in the palette.
+    pub fn colors(self) -> &'static [[u8; 3]] {
+        match self {
+            Palette::GameBoy => &GAMEBOY,
+            Palette::Cga => &CGA,
+            Palette::Grayscale4 => &GRAYSCALE4,
+        }
+    }
+
+    /// The color in the palette closest to `rgb`.
+    pub fn nearest(self, rgb: [u8; 3]) -> [u8; 3] {
+        // Palettes are tiny, so a linear search is plenty
+        *self
+            .colors()
+            .iter()
+            .min_by_key(|&&c| distance(rgb, c))
+            .unwrap()
+    }
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "gameboy" => Ok(Palette::GameBoy),
+            "cga" => Ok(Palette::Cga),
+            "grayscale4" | "greyscale4" => Ok(Palette::Grayscale4),
+            _ => Err(format!(
+                "unknown palette '{}': expected one of gameboy, cga, grayscale4",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_snaps_to_closest_color() {
+        assert_eq!(Palette::Grayscale4.nearest([100, 90, 80]), [85; 3]);
+        assert_eq!(Palette::Grayscale4.nearest([250, 255, 240]), [255; 3]);
+        assert_eq!(Palette::GameBoy.nearest([0, 0, 0]), [15, 56, 15]);
+        // Colors already in the palette are left alone
+        for &c in Palette::Cga.colors() {
+            assert_eq!(Palette::Cga.nearest(c), c);
+        }
+    }
+}
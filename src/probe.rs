@@ -0,0 +1,273 @@
+use std::{fmt, path::Path, process::Command, time::Duration};
+
+use serde_json::Value;
+
+use crate::error::Png2tError;
+
+/// Stream information about a media file, as reported by `ffprobe`.
+///
+/// Details about "the video stream" are about the first one, unless another is picked with `with_video_track`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Probe {
+    /// Whether the file is a still image rather than a video.
+    pub still: bool,
+    /// Dimensions of the video stream.
+    pub dimensions: Option<(u32, u32)>,
+    /// Number of frames in the video stream, if the container records it.
+    pub frames: Option<u64>,
+    /// Length of the file in seconds.
+    pub duration: Option<f32>,
+    /// Framerate of the video stream, if one could be determined.
+    pub fps: Option<f32>,
+    /// Whether the video stream uses an HDR transfer function (PQ or HLG).
+    pub hdr: bool,
+    /// Number of video streams in the file, including any cover art or thumbnails stored as pictures.
+    pub video_tracks: usize,
+    /// Number of audio streams in the file.
+    pub audio_tracks: usize,
+    /// Number of subtitle streams in the file.
+    pub subtitle_tracks: usize,
+    /// Whether the container gives each frame its own delay, as GIFs and APNGs do.
+    pub variable_timing: bool,
+}
+
+impl Probe {
+    /// Runs the `ffprobe` binary at `ffprobe` on `file` and parses its JSON stream and format listing.
+    ///
+    /// # Errors
+    /// Fails if `ffprobe` cannot be run, can't read the file, or produces output that isn't valid JSON.
+    pub fn new(ffprobe: &Path, file: &str) -> Result<Self, Png2tError> {
+        Self::with_video_track(ffprobe, file, 0)
+    }
+
+    /// Like `new`, but describes the video stream numbered `track` (counting from 0) instead of the first one.
+    /// If there's no such stream, the details are left unknown, and `video_tracks` says how many there are.
+    ///
+    /// # Errors
+    /// Fails if `ffprobe` cannot be run, can't read the file, or produces output that isn't valid JSON.
+    pub fn with_video_track(ffprobe: &Path, file: &str, track: usize) -> Result<Self, Png2tError> {
+        let json = run_ffprobe(ffprobe, file, &["-show_streams", "-show_format"])?;
+        Ok(Self::from_json(&json, track))
+    }
+
+    /// Reads how long each frame of video stream `track` (counting from 0) should be shown for, from its packet durations.
+    ///
+    /// Only demuxes the file, so it's quick even for long files. Delays of 10ms or less are bumped up to 100ms,
+    /// since that's how browsers treat them and plenty of GIFs rely on it.
+    ///
+    /// # Errors
+    /// Fails if `ffprobe` can't read the file, or doesn't report a duration for every frame.
+    pub fn frame_durations(
+        ffprobe: &Path,
+        file: &str,
+        track: usize,
+    ) -> Result<Vec<Duration>, Png2tError> {
+        let stream = format!("v:{}", track);
+        let json = run_ffprobe(
+            ffprobe,
+            file,
+            &[
+                "-select_streams",
+                &stream,
+                "-show_entries",
+                "packet=duration_time",
+            ],
+        )?;
+
+        let packets = json["packets"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        let mut durations = Vec::with_capacity(packets.len());
+        for packet in packets {
+            let secs: f64 = match packet["duration_time"]
+                .as_str()
+                .and_then(|d| d.parse().ok())
+            {
+                Some(d) => d,
+                None => {
+                    return Err(Png2tError::decode(
+                        file,
+                        "ffprobe did not report its frame durations",
+                    ))
+                }
+            };
+
+            durations.push(match secs > 0.01 {
+                true => Duration::from_secs_f64(secs),
+                false => Duration::from_millis(100),
+            });
+        }
+
+        Ok(durations)
+    }
+
+    /// Pulls the interesting fields out of `ffprobe -print_format json -show_streams -show_format` output.
+    /// `track` picks which video stream to describe.
+    fn from_json(json: &Value, track: usize) -> Self {
+        let streams = json["streams"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        // Only one video stream matters - files may also carry audio, subtitles, etc.
+        let video = streams
+            .iter()
+            .filter(|s| s["codec_type"].as_str() == Some("video"))
+            .nth(track);
+
+        // `r_frame_rate` is the "real" base framerate, but some containers leave it at 0/0 and only fill `avg_frame_rate`
+        let fps = video.and_then(|v| {
+            ["r_frame_rate", "avg_frame_rate"]
+                .iter()
+                .find_map(|key| v[key].as_str().and_then(parse_rational))
+        });
+
+        let hdr = video.is_some_and(|v| {
+            matches!(
+                v["color_transfer"].as_str(),
+                Some("smpte2084" | "arib-std-b67")
+            )
+        });
+
+        let dimensions = video.and_then(|v| {
+            let w = v["width"].as_u64()?;
+            let h = v["height"].as_u64()?;
+            Some((w as u32, h as u32))
+        });
+
+        // ffprobe reports these as strings rather than numbers
+        let frames = video.and_then(|v| v["nb_frames"].as_str()?.parse().ok());
+        let duration = video
+            .and_then(|v| v["duration"].as_str())
+            .or_else(|| json["format"]["duration"].as_str())
+            .and_then(|d| d.parse().ok());
+
+        // Still images are read with the `image2` demuxer or one of the single-image `*_pipe` ones
+        let format = json["format"]["format_name"].as_str().unwrap_or_default();
+        let still = format == "image2" || format.ends_with("_pipe") || frames == Some(1);
+
+        let tracks = |kind: &str| {
+            streams
+                .iter()
+                .filter(|s| s["codec_type"].as_str() == Some(kind))
+                .count()
+        };
+        let video_tracks = tracks("video");
+        let audio_tracks = tracks("audio");
+        let subtitle_tracks = tracks("subtitle");
+
+        let variable_timing = matches!(format, "gif" | "apng");
+
+        Probe {
+            still,
+            dimensions,
+            frames,
+            duration,
+            fps,
+            hdr,
+            video_tracks,
+            audio_tracks,
+            subtitle_tracks,
+            variable_timing,
+        }
+    }
+}
+
+impl fmt::Display for Probe {
+    /// Formats the probe as a human-readable summary, one property per line.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        /// Writes an optional value, or "unknown" if it's missing.
+        fn or_unknown<T: fmt::Display>(v: Option<T>) -> String {
+            v.map_or_else(|| String::from("unknown"), |v| v.to_string())
+        }
+
+        /// Describes how many tracks of something there are.
+        fn tracks(n: usize) -> String {
+            match n {
+                0 => String::from("none"),
+                1 => String::from("1 track"),
+                n => format!("{} tracks", n),
+            }
+        }
+
+        writeln!(
+            f,
+            "Type:       {}",
+            match self.still {
+                true => "image",
+                false => "video",
+            }
+        )?;
+        writeln!(
+            f,
+            "Resolution: {}",
+            or_unknown(self.dimensions.map(|(w, h)| format!("{}x{}", w, h)))
+        )?;
+
+        if !self.still {
+            writeln!(f, "Frames:     {}", or_unknown(self.frames))?;
+            writeln!(
+                f,
+                "Duration:   {}",
+                or_unknown(self.duration.map(|d| format!("{:.2}s", d)))
+            )?;
+            writeln!(
+                f,
+                "Framerate:  {}",
+                or_unknown(self.fps.map(|fps| format!("{:.3} fps", fps)))
+            )?;
+        }
+
+        writeln!(f, "HDR:        {}", if self.hdr { "yes" } else { "no" })?;
+        writeln!(f, "Video:      {}", tracks(self.video_tracks))?;
+        writeln!(f, "Audio:      {}", tracks(self.audio_tracks))?;
+        write!(f, "Subtitles:  {}", tracks(self.subtitle_tracks))
+    }
+}
+
+/// Runs the `ffprobe` binary at `ffprobe` on `file` with JSON output, plus whatever `args` select what to show.
+///
+/// # Errors
+/// Fails if `ffprobe` cannot be run, can't read the file, or produces output that isn't valid JSON.
+fn run_ffprobe(ffprobe: &Path, file: &str, args: &[&str]) -> Result<Value, Png2tError> {
+    let output = match Command::new(ffprobe)
+        .args(["-v", "quiet", "-print_format", "json"])
+        .args(args)
+        .arg(file)
+        .output()
+    {
+        Ok(o) => o,
+        Err(e) => {
+            return Err(Png2tError::io(
+                format!("Unable to run ffprobe on {}", file),
+                e,
+            ))
+        }
+    };
+
+    if !output.status.success() {
+        return Err(Png2tError::decode(file, "ffprobe was unable to read it"));
+    }
+
+    match serde_json::from_slice(&output.stdout) {
+        Ok(v) => Ok(v),
+        Err(e) => Err(Png2tError::decode(
+            file,
+            format!("ffprobe's output couldn't be parsed ({})", e),
+        )),
+    }
+}
+
+/// Evaluates an ffprobe rational such as `30000/1001` (or a plain number) to a float.
+///
+/// Returns `None` for anything unparseable or not strictly positive, which includes ffprobe's `0/0` placeholder.
+fn parse_rational(s: &str) -> Option<f32> {
+    let value = match s.split_once('/') {
+        Some((num, den)) => num.trim().parse::<f64>().ok()? / den.trim().parse::<f64>().ok()?,
+        None => s.trim().parse::<f64>().ok()?,
+    };
+
+    (value.is_finite() && value > 0.0).then_some(value as f32)
+}
@@ -0,0 +1,78 @@
+use std::{fs::File, io::BufWriter, path::Path, time::Duration};
+
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    Delay, Frame, Rgba,
+};
+
+use crate::{
+    error::Png2tError,
+    render::{resolve, RenderOptions},
+    Image,
+};
+
+/// Each image pixel is drawn as a square this many pixels across, making each cell 8x16 like a typical terminal font.
+const PIXEL_SIZE: u32 = 8;
+
+/// Saves frames to an animated GIF at `path`, looking the way they would in the terminal.
+///
+/// Colors go through the same transparency handling and palette as `render_image`. Transparent pixels are drawn in
+/// the terminal's background color if it's known and black otherwise, since GIFs can't blend partial transparency.
+///
+/// # Errors
+/// Fails if `path` can't be written to, or the GIF can't be encoded.
+pub fn record_gif(
+    frames: &[Image],
+    delays: &[Duration],
+    opts: &RenderOptions,
+    path: &Path,
+) -> Result<(), Png2tError> {
+    let file = match File::create(path) {
+        Ok(f) => f,
+        Err(e) => {
+            return Err(Png2tError::io(
+                format!("Unable to create {}", path.display()),
+                e,
+            ))
+        }
+    };
+
+    let mut encoder = GifEncoder::new(BufWriter::new(file));
+    let res = encoder.set_repeat(Repeat::Infinite).and_then(|_| {
+        encoder.encode_frames(frames.iter().zip(delays).map(|(frame, &delay)| {
+            Frame::from_parts(
+                rasterize(frame, opts),
+                0,
+                0,
+                Delay::from_saturating_duration(delay),
+            )
+        }))
+    });
+
+    if let Err(e) = res {
+        return Err(Png2tError::Encode {
+            context: format!("Unable to record to {}", path.display()),
+            source: e,
+        });
+    }
+
+    Ok(())
+}
+
+/// Draws a frame as the terminal would show it, scaled up so each pixel is `PIXEL_SIZE` across.
+fn rasterize(img: &Image, opts: &RenderOptions) -> Image {
+    let backdrop = opts.terminal_background.unwrap_or([0, 0, 0]);
+    let (w, h) = img.dimensions();
+
+    // Odd heights still take up whole cells, with a blank bottom half in the last row
+    Image::from_fn(w * PIXEL_SIZE, h.div_ceil(2) * 2 * PIXEL_SIZE, |x, y| {
+        let (px, py) = (x / PIXEL_SIZE, y / PIXEL_SIZE);
+        let rgb = match py < h {
+            true => resolve(*img.get_pixel(px, py), opts),
+            false => None,
+        }
+        .map_or(backdrop, |rgb| opts.color_mode.displayed(rgb));
+
+        Rgba([rgb[0], rgb[1], rgb[2], u8::MAX])
+    })
+}
@@ -0,0 +1,748 @@
+use std::{borrow::Cow, fmt::Write, str::FromStr};
+
+use crossterm::cursor::{MoveDown, MoveRight, MoveToColumn};
+use image::Rgba;
+use serde_json::{json, Value};
+
+use crate::{color::ColorMode, lut, palette::Palette, Image};
+
+/// Which half-block character draws a pair of pixels that are both showing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Glyph {
+    /// `▄`, colored with the lower pixel and sitting on the upper one as the background.
+    #[default]
+    Lower,
+    /// `▀`, colored with the upper pixel and sitting on the lower one as the background.
+    Upper,
+}
+
+impl FromStr for Glyph {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "lower" => Ok(Glyph::Lower),
+            "upper" => Ok(Glyph::Upper),
+            _ => Err(format!(
+                "unknown glyph '{}': expected one of lower, upper",
+                s
+            )),
+        }
+    }
+}
+
+/// Options controlling how an image is turned into terminal output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderOptions {
+    /// The set of colors to emit.
+    pub color_mode: ColorMode,
+    /// Color to blend transparent pixels over. When `None`, transparent pixels show the terminal's own background.
+    pub background: Option<[u8; 3]>,
+    /// The terminal's own background color, if known. Only used to blend the edges of partially transparent
+    /// pixels, since fully transparent ones already show the real thing.
+    pub terminal_background: Option<[u8; 3]>,
+    /// Terminal column each row starts at. The first row starts wherever the cursor already is.
+    pub column: u16,
+    /// Invert all color. Applied as frames are drawn rather than ahead of time, so it can be toggled during playback.
+    pub invert: bool,
+    /// Invert in linear light rather than on the sRGB values. See `lut::invert_linear`.
+    pub linear_invert: bool,
+    /// The palette frames were snapped to, if any. Inverted colors are snapped back into it, so they stay palette colors.
+    pub palette: Option<Palette>,
+    /// Characters to draw fully transparent cells with, alternating between the two in a checkerboard.
+    /// Drawn in the terminal's default color. Both are spaces by default, which leaves nothing visible.
+    pub transparent: [char; 2],
+    /// The character to draw with where both pixels of a cell are showing. Some fonts line one up better than the other.
+    pub glyph: Glyph,
+    /// Pick each cell's glyph and colors to best match the area it covers, including how it blends into the cells
+    /// either side. See `best_fit_cell`.
+    pub best_fit: bool,
+    /// End each row with a newline instead of moving the cursor, for output that isn't going to a terminal.
+    /// `column` is ignored, and frames no longer draw in place.
+    pub newlines: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            color_mode: ColorMode::TrueColor,
+            background: None,
+            terminal_background: None,
+            column: 0,
+            invert: false,
+            linear_invert: false,
+            palette: None,
+            transparent: [' '; 2],
+            glyph: Glyph::Lower,
+            best_fit: false,
+            newlines: false,
+        }
+    }
+}
+
+/// Renders an image into a string of ANSI escapes and half-block characters.
+///
+/// Every character represents two vertically stacked pixels. Each row of characters is followed by
+/// a cursor move down to `opts.column` on the next line, so the output draws in place starting from the
+/// current cursor position rather than scrolling the terminal.
+/// Colors are only set when they change from the previous character, which keeps flat areas cheap to print.
+pub fn render_image(img: &Image, opts: &RenderOptions) -> String {
+    render_cells(img, None, opts)
+}
+
+/// Renders only the parts of `img` that differ from `prev`, assuming `prev` is already on screen at the cursor.
+///
+/// Unchanged characters are skipped over with cursor moves, which makes videos that are mostly still much cheaper to print.
+/// Falls back to drawing everything if the frames aren't the same size.
+pub fn render_image_diff(img: &Image, prev: &Image, opts: &RenderOptions) -> String {
+    match img.dimensions() == prev.dimensions() {
+        true => render_cells(img, Some(prev), opts),
+        false => render_cells(img, None, opts),
+    }
+}
+
+/// Does the work for `render_image` and `render_image_diff`. `prev` must be the same size as `img` if given.
+fn render_cells(img: &Image, prev: Option<&Image>, opts: &RenderOptions) -> String {
+    let mode = opts.color_mode;
+    let mut out = String::new();
+    let mut pen = Pen::default();
+
+    let (w, h) = img.dimensions();
+    for y in (0..h).step_by(2) {
+        // Characters that haven't changed since `prev` and still need to be stepped over
+        let mut skipped = 0;
+
+        for x in 0..w {
+            // Best-fit cells also depend on the pixels either side of them
+            let columns = match opts.best_fit {
+                true => x.saturating_sub(1)..(x + 2).min(w),
+                false => x..x + 1,
+            };
+            let unchanged = prev.is_some_and(|prev| {
+                columns.clone().all(|x| {
+                    (y..(y + 2).min(h)).all(|y| img.get_pixel(x, y) == prev.get_pixel(x, y))
+                })
+            });
+            if unchanged {
+                skipped += 1;
+                continue;
+            }
+
+            // Moving the cursor doesn't paint anything, so the colors can be left as they are
+            if skipped > 0 {
+                write!(out, "{}", MoveRight(skipped)).unwrap();
+                skipped = 0;
+            }
+
+            let cell = cell(img, x, y, opts);
+            pen.bg(&mut out, cell.bg.map(|bg| mode.bg(bg)));
+            // The foreground doesn't show on a space, so whatever it's set to can stay
+            match cell.fg {
+                Some(fg) => pen.fg(&mut out, mode.fg(fg)),
+                None if cell.glyph != ' ' => pen.fg(&mut out, String::from("\x1b[39m")),
+                None => {}
+            }
+            out.push(cell.glyph);
+        }
+
+        pen.reset(&mut out);
+        end_row(&mut out, opts);
+    }
+
+    out
+}
+
+/// Finishes a row of output, getting ready to print the next one underneath.
+fn end_row(out: &mut String, opts: &RenderOptions) {
+    match opts.newlines {
+        true => out.push('\n'),
+        // Keep the cursor in the right position to print the next row. Writing into a `String` can't fail.
+        false => write!(out, "{}{}", MoveDown(1), MoveToColumn(opts.column)).unwrap(),
+    }
+}
+
+/// One character of half-block output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cell {
+    glyph: char,
+    /// Foreground color, or `None` if it doesn't matter because nothing is drawn in it.
+    fg: Option<[u8; 3]>,
+    /// Background color, or `None` to leave the terminal's own background showing.
+    bg: Option<[u8; 3]>,
+}
+
+/// Works out how to draw the character covering pixels `(x, y)` and `(x, y + 1)` of `img`.
+fn cell(img: &Image, x: u32, y: u32, opts: &RenderOptions) -> Cell {
+    let upper = resolve(*img.get_pixel(x, y), opts);
+    // An odd height leaves the bottom row without a partner, so pretend it's sitting above a transparent pixel
+    let lower = match y + 1 < img.height() {
+        true => resolve(*img.get_pixel(x, y + 1), opts),
+        false => None,
+    };
+
+    // This match is to handle image transparency, but the first case is the simplest to understand.
+    // Using the unicode ▄ symbol, we can use ANSI colors to color its foreground and background.
+    // This means that each character in the terminal can represent two pixels, one higher and one lower.
+    // A `None` pixel should be transparent, so we leave the foreground/background uncolored
+    // or use other means to keep that pixel transparent.
+    let (glyph, fg, bg) = match (upper, lower) {
+        (Some(upper), Some(lower)) if opts.best_fit => {
+            return best_fit_cell(img, x, y, [upper, lower], opts)
+        }
+        (Some(upper), Some(lower)) => match opts.glyph {
+            Glyph::Lower => ('▄', Some(lower), Some(upper)),
+            Glyph::Upper => ('▀', Some(upper), Some(lower)),
+        },
+        (None, None) => (transparent_glyph(x, y, opts), None, None),
+        (Some(upper), None) => ('▀', Some(upper), None),
+        (None, Some(lower)) => ('▄', Some(lower), None),
+    };
+    Cell { glyph, fg, bg }
+}
+
+/// How much less squared error (summed over a cell's four quarters and their channels) drawing a cell in two colors
+/// has to make than drawing it in one, to be worth it. Flat areas then come out in one color, which is cheaper to print.
+const SPLIT_PENALTY: u32 = 4 * 3 * 4 * 4;
+
+/// Works out the glyph and two colors that best match the fully opaque cell covering pixels `(x, y)` and `(x, y + 1)`,
+/// whose colors are `own`.
+///
+/// The cell is split into quarters. Each half of a pixel takes a quarter of the color of the pixel beside it,
+/// as if the image were stretched out smoothly, so edges between cells can fall partway through one.
+/// Every way of splitting the quarters that a glyph can draw (top and bottom with ▀ or ▄, left and right with ▌ or ▐,
+/// or all together with a space or █) is then tried with the average color of each side, and whichever is closest wins.
+/// A glyph and its opposite draw the same split with the colors swapped, so only one of each pair needs trying.
+fn best_fit_cell(img: &Image, x: u32, y: u32, own: [[u8; 3]; 2], opts: &RenderOptions) -> Cell {
+    // Transparent neighbors have no color to blend in, so the cell's own is used instead
+    let pixel = |x: u32, y: u32| resolve(*img.get_pixel(x, y), opts);
+    let beside = |x: Option<u32>| {
+        let x = x.filter(|&x| x < img.width());
+        [0, 1].map(|dy| x.and_then(|x| pixel(x, y + dy)).unwrap_or(own[dy as usize]))
+    };
+    let (left, right) = (beside(x.checked_sub(1)), beside(x.checked_add(1)));
+
+    // Top left, top right, bottom left, bottom right
+    let blend = |near: [u8; 3], own: [u8; 3]| {
+        [0, 1, 2].map(|c| ((near[c] as u32 + own[c] as u32 * 3 + 2) / 4) as u8)
+    };
+    let quarters = [
+        blend(left[0], own[0]),
+        blend(right[0], own[0]),
+        blend(left[1], own[1]),
+        blend(right[1], own[1]),
+    ];
+
+    // Each candidate says which quarters are drawn in the foreground color
+    let (half, half_fg) = match opts.glyph {
+        Glyph::Lower => ('▄', [false, false, true, true]),
+        Glyph::Upper => ('▀', [true, true, false, false]),
+    };
+    let candidates = [
+        (' ', [false; 4]),
+        (half, half_fg),
+        ('▌', [true, false, true, false]),
+    ];
+
+    let (glyph, fg, bg, _) = candidates
+        .into_iter()
+        .map(|(glyph, in_fg)| {
+            let average = |fg: bool| {
+                let side: Vec<[u8; 3]> = (0..4)
+                    .filter(|&i| in_fg[i] == fg)
+                    .map(|i| quarters[i])
+                    .collect();
+                (!side.is_empty()).then(|| {
+                    let n = side.len() as u32;
+                    [0, 1, 2].map(|c| {
+                        ((side.iter().map(|p| p[c] as u32).sum::<u32>() + n / 2) / n) as u8
+                    })
+                })
+            };
+            let (fg, bg) = (average(true), average(false));
+
+            let error: u32 = (0..4)
+                .map(|i| {
+                    let drawn = match in_fg[i] {
+                        true => fg.unwrap(),
+                        false => bg.unwrap(),
+                    };
+                    (0..3)
+                        .map(|c| (drawn[c] as i32 - quarters[i][c] as i32).pow(2) as u32)
+                        .sum::<u32>()
+                })
+                .sum();
+            let penalty = match fg {
+                Some(_) => SPLIT_PENALTY,
+                None => 0,
+            };
+            (glyph, fg, bg, error + penalty)
+        })
+        .min_by_key(|&(.., error)| error)
+        .unwrap();
+
+    Cell { glyph, fg, bg }
+}
+
+/// The character for a fully transparent cell at pixel `(x, y)`, picked from `opts.transparent` in a checkerboard.
+fn transparent_glyph(x: u32, y: u32, opts: &RenderOptions) -> char {
+    // Each row of characters covers two rows of pixels
+    opts.transparent[((x + y / 2) % 2) as usize]
+}
+
+/// Parses the characters to draw transparent cells with: one to use everywhere, or two to alternate between.
+///
+/// # Errors
+/// Fails unless there are one or two characters, neither of which are control characters.
+pub fn parse_transparent(s: &str) -> Result<[char; 2], String> {
+    let chars: Vec<char> = s.chars().collect();
+    if chars.iter().any(|c| c.is_control()) {
+        return Err(String::from("control characters can't be drawn"));
+    }
+
+    match chars[..] {
+        [c] => Ok([c; 2]),
+        [a, b] => Ok([a, b]),
+        _ => Err(format!("expected 1 or 2 characters, found {}", chars.len())),
+    }
+}
+
+/// Describes how `render_image` would draw `img` as JSON, so other programs can draw it their own way.
+///
+/// Gives an object with the `width` and `height` in characters, and `cells` holding each row of characters in turn.
+/// Every cell has its `glyph`, plus `fg` and `bg` colors as `[r, g, b]`, or `null` where the terminal's own shows through.
+/// Colors are the ones the terminal would actually show in `opts.color_mode`.
+pub fn render_json(img: &Image, opts: &RenderOptions) -> Value {
+    let (w, h) = img.dimensions();
+    let color = |c: Option<[u8; 3]>| c.map(|c| opts.color_mode.displayed(c));
+
+    let rows: Vec<Value> = (0..h)
+        .step_by(2)
+        .map(|y| {
+            (0..w)
+                .map(|x| {
+                    let cell = cell(img, x, y, opts);
+                    json!({
+                        "glyph": cell.glyph.to_string(),
+                        "fg": color(cell.fg),
+                        "bg": color(cell.bg),
+                    })
+                })
+                .collect()
+        })
+        .collect();
+
+    json!({
+        "width": w,
+        "height": h.div_ceil(2),
+        "cells": rows,
+    })
+}
+
+/// Keeps track of the colors the terminal is currently set to draw with, so escapes are only written when they change.
+#[derive(Default)]
+struct Pen {
+    fg: Option<String>,
+    bg: Option<String>,
+}
+
+impl Pen {
+    /// Switches to the foreground color set by `escape`.
+    fn fg(&mut self, out: &mut String, escape: String) {
+        if self.fg.as_ref() != Some(&escape) {
+            out.push_str(&escape);
+            self.fg = Some(escape);
+        }
+    }
+
+    /// Switches to the background color set by `escape`, or back to the terminal's own background for `None`.
+    fn bg(&mut self, out: &mut String, escape: Option<String>) {
+        if self.bg != escape {
+            out.push_str(escape.as_deref().unwrap_or("\x1b[49m"));
+            self.bg = escape;
+        }
+    }
+
+    /// Goes back to the terminal's default colors, if they've been changed.
+    fn reset(&mut self, out: &mut String) {
+        if self.fg.is_some() || self.bg.is_some() {
+            out.push_str("\x1b[0m");
+            *self = Pen::default();
+        }
+    }
+}
+
+/// The default characters for `render_ascii`, from darkest to brightest.
+pub const ASCII_RAMP: &str = " .:-=+*#%@";
+
+/// Ramps that can be picked by name instead of spelling them out.
+const ASCII_RAMP_PRESETS: [(&str, &str); 2] = [("classic", ASCII_RAMP), ("blocks", " ░▒▓█")];
+
+/// Parses a ramp of characters for `render_ascii`, ordered from darkest to brightest, or the name of a preset
+/// (`classic` or `blocks`).
+///
+/// # Errors
+/// Fails if there are fewer than 2 characters, or any control characters.
+pub fn parse_ascii_ramp(s: &str) -> Result<Vec<char>, String> {
+    if let Some((_, ramp)) = ASCII_RAMP_PRESETS.iter().find(|(name, _)| *name == s) {
+        return Ok(ramp.chars().collect());
+    }
+
+    let ramp: Vec<char> = s.chars().collect();
+    if ramp.iter().any(|c| c.is_control()) {
+        return Err(String::from("control characters can't be drawn"));
+    }
+    if ramp.len() < 2 {
+        return Err(format!(
+            "expected at least 2 characters or a preset ({}), found {}",
+            ASCII_RAMP_PRESETS.map(|(name, _)| name).join(", "),
+            ramp.len()
+        ));
+    }
+
+    Ok(ramp)
+}
+
+/// Renders an image into plain ASCII art, with no color at all.
+///
+/// Like `render_image`, each character covers two vertically stacked pixels and rows are separated by cursor moves.
+/// The character is picked from `ramp`, which goes from darkest to brightest, by the pair's average brightness.
+/// This assumes light text on a dark terminal. `ramp` can't be empty.
+pub fn render_ascii(img: &Image, opts: &RenderOptions, ramp: &[char]) -> String {
+    let mut out = String::new();
+
+    let (w, h) = img.dimensions();
+    for y in (0..h).step_by(2) {
+        for x in 0..w {
+            // Transparent pixels don't count towards the brightness. If both are transparent, leave a gap.
+            let lum: Vec<f32> = [y, y + 1]
+                .iter()
+                .filter(|&&y| y < h)
+                .filter_map(|&y| resolve(*img.get_pixel(x, y), opts))
+                .map(luminance)
+                .collect();
+
+            match lum.is_empty() {
+                true => out.push(transparent_glyph(x, y, opts)),
+                false => {
+                    let avg = lum.iter().sum::<f32>() / lum.len() as f32;
+                    let idx = (avg * (ramp.len() - 1) as f32).round() as usize;
+                    out.push(ramp[idx]);
+                }
+            }
+        }
+
+        end_row(&mut out, opts);
+    }
+
+    out
+}
+
+/// Relative luminance of a color from 0 to 1, per Rec. 709. Close enough to work on sRGB values directly for picking characters.
+pub(crate) fn luminance(rgb: [u8; 3]) -> f32 {
+    (0.2126 * rgb[0] as f32 + 0.7152 * rgb[1] as f32 + 0.0722 * rgb[2] as f32) / 255.0
+}
+
+/// Works out the color a pixel should be drawn with, or `None` if it should be left transparent.
+///
+/// Partially transparent pixels are blended over the background when one is known. Otherwise there's
+/// nothing to blend with, so they're drawn as-is if they're mostly opaque and left out if not.
+pub(crate) fn resolve(mut pixel: Rgba<u8>, opts: &RenderOptions) -> Option<[u8; 3]> {
+    // Inverting the image shouldn't invert the background it's blended with, so it has to come first
+    if opts.invert {
+        invert_pixel(&mut pixel, opts.linear_invert, opts.palette);
+    }
+
+    if let Some(bg) = opts.background {
+        return Some(composite(pixel, bg));
+    }
+
+    match pixel[3] {
+        0 => None,
+        u8::MAX => Some(rgb(&pixel)),
+        a => match opts.terminal_background {
+            Some(bg) => Some(composite(pixel, bg)),
+            None if a >= 128 => Some(rgb(&pixel)),
+            None => None,
+        },
+    }
+}
+
+/// Blends a pixel over an opaque background color according to its alpha.
+fn composite(pixel: Rgba<u8>, background: [u8; 3]) -> [u8; 3] {
+    let a = pixel[3] as u32;
+    let blend = |c: u8, bg: u8| ((c as u32 * a + bg as u32 * (255 - a) + 127) / 255) as u8;
+
+    [
+        blend(pixel[0], background[0]),
+        blend(pixel[1], background[1]),
+        blend(pixel[2], background[2]),
+    ]
+}
+
+/// Inverts a pixel's color, leaving its alpha alone. `linear` inverts it in linear light.
+///
+/// The inverse of a palette color usually isn't one itself, so it's snapped back to the nearest color in `palette`.
+pub(crate) fn invert_pixel(pixel: &mut Rgba<u8>, linear: bool, palette: Option<Palette>) {
+    let lut = lut::inversion(linear);
+    for c in &mut pixel.0[..3] {
+        *c = lut.get(*c);
+    }
+
+    if let Some(palette) = palette {
+        let snapped = palette.nearest(rgb(pixel));
+        pixel.0[..3].copy_from_slice(&snapped);
+    }
+}
+
+/// Gives `img` with its colors inverted if `invert` is set, or `img` itself (without copying it) if not.
+/// `linear` inverts it in linear light, and colors are snapped back into `palette` as in `invert_pixel`.
+pub(crate) fn inverted_if(
+    img: &Image,
+    invert: bool,
+    linear: bool,
+    palette: Option<Palette>,
+) -> Cow<'_, Image> {
+    match invert {
+        true => {
+            let mut img = img.clone();
+            for pixel in img.pixels_mut() {
+                invert_pixel(pixel, linear, palette);
+            }
+            Cow::Owned(img)
+        }
+        false => Cow::Borrowed(img),
+    }
+}
+
+/// Strips the alpha channel off of a pixel.
+fn rgb(pixel: &Rgba<u8>) -> [u8; 3] {
+    [pixel[0], pixel[1], pixel[2]]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_colors_are_only_set_once() {
+        let img = Image::from_pixel(8, 4, Rgba([255, 0, 0, 255]));
+        let out = render_image(&img, &RenderOptions::default());
+
+        // One row is two lines of pixels, and each row resets and sets the colors again
+        assert_eq!(out.matches("\x1b[48;2;255;0;0m").count(), 2);
+        assert_eq!(out.matches("\x1b[38;2;255;0;0m").count(), 2);
+        assert_eq!(out.matches('▄').count(), 16);
+    }
+
+    #[test]
+    fn checkerboard_renders_exactly() {
+        let (black, white) = (Rgba([0, 0, 0, 255]), Rgba([255, 255, 255, 255]));
+        let img = Image::from_fn(2, 2, |x, y| if (x + y) % 2 == 0 { black } else { white });
+
+        assert_eq!(
+            render_image(&img, &RenderOptions::default()),
+            "\x1b[48;2;0;0;0m\x1b[38;2;255;255;255m▄\x1b[48;2;255;255;255m\x1b[38;2;0;0;0m▄\x1b[0m\x1b[1B\x1b[1G"
+        );
+
+        let opts = RenderOptions {
+            glyph: Glyph::Upper,
+            ..RenderOptions::default()
+        };
+        assert_eq!(
+            render_image(&img, &opts),
+            "\x1b[48;2;255;255;255m\x1b[38;2;0;0;0m▀\x1b[48;2;0;0;0m\x1b[38;2;255;255;255m▀\x1b[0m\x1b[1B\x1b[1G"
+        );
+
+        let opts = RenderOptions {
+            color_mode: ColorMode::Ansi256,
+            column: 4,
+            ..RenderOptions::default()
+        };
+        assert_eq!(
+            render_image(&img, &opts),
+            "\x1b[48;5;16m\x1b[38;5;231m▄\x1b[48;5;231m\x1b[38;5;16m▄\x1b[0m\x1b[1B\x1b[5G"
+        );
+    }
+
+    #[test]
+    fn transparency_picks_glyphs_and_backgrounds() {
+        let (red, blue, clear) = (
+            Rgba([255, 0, 0, 255]),
+            Rgba([0, 0, 255, 255]),
+            Rgba([0, 0, 0, 0]),
+        );
+        let mut img = Image::from_pixel(3, 2, clear);
+        img.put_pixel(0, 0, red);
+        img.put_pixel(2, 1, blue);
+
+        // Only the opaque half of each character is colored, and the terminal's background shows through the rest
+        assert_eq!(
+            render_image(&img, &RenderOptions::default()),
+            "\x1b[38;2;255;0;0m▀ \x1b[38;2;0;0;255m▄\x1b[0m\x1b[1B\x1b[1G"
+        );
+
+        // With a background to blend over, nothing is left transparent
+        let opts = RenderOptions {
+            background: Some([0, 255, 0]),
+            ..RenderOptions::default()
+        };
+        assert_eq!(
+            render_image(&img, &opts),
+            "\x1b[48;2;255;0;0m\x1b[38;2;0;255;0m▄\x1b[48;2;0;255;0m▄\x1b[38;2;0;0;255m▄\x1b[0m\x1b[1B\x1b[1G"
+        );
+    }
+
+    #[test]
+    fn invert_keeps_alpha() {
+        let img = Image::from_pixel(1, 1, Rgba([255, 100, 0, 128]));
+        assert_eq!(
+            inverted_if(&img, true, false, None).get_pixel(0, 0),
+            &Rgba([0, 155, 255, 128])
+        );
+        assert!(matches!(
+            inverted_if(&img, false, true, None),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn inverting_keeps_to_the_palette() {
+        let palette = Palette::GameBoy;
+        let colors = palette.colors();
+        let img = Image::from_fn(4, 1, |x, _| {
+            let [r, g, b] = colors[x as usize];
+            Rgba([r, g, b, 255])
+        });
+
+        for linear in [false, true] {
+            let inverted = inverted_if(&img, true, linear, Some(palette));
+            assert!(inverted.pixels().all(|p| colors.contains(&rgb(p))));
+
+            let opts = RenderOptions {
+                invert: true,
+                linear_invert: linear,
+                palette: Some(palette),
+                ..RenderOptions::default()
+            };
+            assert!(img
+                .pixels()
+                .all(|p| colors.contains(&resolve(*p, &opts).unwrap())));
+        }
+    }
+
+    #[test]
+    fn best_fit_follows_edges_within_cells() {
+        let opts = RenderOptions {
+            best_fit: true,
+            ..RenderOptions::default()
+        };
+        let opaque = |c: u8| Rgba([c, c, c, 255]);
+
+        // A gray column between black and white is darker on its left than its right
+        let img = Image::from_fn(3, 2, |x, _| opaque([0, 128, 255][x as usize]));
+        let drawn = cell(&img, 1, 0, &opts);
+        assert_eq!(drawn.glyph, '▌');
+        assert_eq!((drawn.fg, drawn.bg), (Some([96; 3]), Some([160; 3])));
+
+        // Flat areas share one color, while a change from top to bottom still splits the cell that way
+        let img = Image::from_pixel(3, 2, opaque(128));
+        let drawn = cell(&img, 1, 0, &opts);
+        assert_eq!(
+            (drawn.glyph, drawn.fg, drawn.bg),
+            (' ', None, Some([128; 3]))
+        );
+
+        let img = Image::from_fn(1, 2, |_, y| opaque([0, 255][y as usize]));
+        let drawn = cell(&img, 0, 0, &opts);
+        assert_eq!(
+            (drawn.glyph, drawn.fg, drawn.bg),
+            ('▄', Some([255; 3]), Some([0; 3]))
+        );
+    }
+
+    #[test]
+    fn transparent_cells_alternate_glyphs() {
+        let opts = RenderOptions {
+            transparent: ['.', ':'],
+            ..RenderOptions::default()
+        };
+        let img = Image::new(3, 4);
+        assert_eq!(
+            render_image(&img, &opts),
+            "\x1b[39m.:.\x1b[0m\x1b[1B\x1b[1G\x1b[39m:.:\x1b[0m\x1b[1B\x1b[1G"
+        );
+
+        assert_eq!(parse_transparent("#"), Ok(['#'; 2]));
+        assert!(parse_transparent("").is_err());
+        assert!(parse_transparent("abc").is_err());
+        assert!(parse_transparent("\t").is_err());
+    }
+
+    #[test]
+    fn newlines_replace_cursor_moves() {
+        let img = Image::from_pixel(1, 4, Rgba([255, 0, 0, 255]));
+        let opts = RenderOptions {
+            newlines: true,
+            column: 3,
+            ..RenderOptions::default()
+        };
+        let out = render_image(&img, &opts);
+
+        assert_eq!(out.matches('\n').count(), 2);
+        assert!(!out.contains("\x1b[1B"));
+    }
+
+    #[test]
+    fn diff_only_draws_changed_cells() {
+        let prev = Image::from_pixel(4, 4, Rgba([255, 0, 0, 255]));
+        assert!(!render_image_diff(&prev, &prev, &RenderOptions::default()).contains('▄'));
+
+        let mut img = prev.clone();
+        img.put_pixel(2, 3, Rgba([0, 0, 255, 255]));
+        let out = render_image_diff(&img, &prev, &RenderOptions::default());
+        assert_eq!(out.matches('▄').count(), 1);
+        assert!(out.contains("\x1b[38;2;0;0;255m"));
+    }
+
+    #[test]
+    fn json_describes_every_cell() {
+        let mut img = Image::from_pixel(2, 3, Rgba([255, 0, 0, 255]));
+        img.put_pixel(1, 0, Rgba([0, 0, 0, 0]));
+        let out = render_json(&img, &RenderOptions::default());
+
+        assert_eq!(out["width"], 2);
+        assert_eq!(out["height"], 2);
+        assert_eq!(
+            out["cells"][0][0],
+            json!({"glyph": "▄", "fg": [255, 0, 0], "bg": [255, 0, 0]})
+        );
+        assert_eq!(
+            out["cells"][0][1],
+            json!({"glyph": "▄", "fg": [255, 0, 0], "bg": null})
+        );
+        // The last row has nothing below it
+        assert_eq!(
+            out["cells"][1][0],
+            json!({"glyph": "▀", "fg": [255, 0, 0], "bg": null})
+        );
+    }
+
+    #[test]
+    fn ascii_follows_brightness_and_skips_transparency() {
+        let img = Image::from_fn(3, 2, |x, _| match x {
+            0 => Rgba([0, 0, 0, 0]),
+            1 => Rgba([0, 0, 0, 255]),
+            _ => Rgba([255, 255, 255, 255]),
+        });
+        let ramp: Vec<char> = ASCII_RAMP.chars().collect();
+        let out = render_ascii(&img, &RenderOptions::default(), &ramp);
+
+        assert!(out.starts_with("  @"));
+        assert!(!out.contains("\x1b[38"));
+
+        let blocks = parse_ascii_ramp("blocks").unwrap();
+        assert!(render_ascii(&img, &RenderOptions::default(), &blocks).starts_with("  █"));
+        assert_eq!(parse_ascii_ramp(".#"), Ok(vec!['.', '#']));
+        assert!(parse_ascii_ramp("#").is_err());
+    }
+}
@@ -0,0 +1,161 @@
+use std::{fs, path::Path, time::Duration};
+
+use crate::error::Png2tError;
+
+/// One subtitle, shown from `start` until just before `end`, measured from the start of the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Cue {
+    pub start: Duration,
+    pub end: Duration,
+    /// What to show, one entry per line.
+    pub lines: Vec<String>,
+}
+
+/// Reads the SubRip (.srt) file at `path`. See `parse_srt`.
+///
+/// # Errors
+/// Fails if the file can't be read, or isn't valid SubRip.
+pub(crate) fn load_srt(path: &Path) -> Result<Vec<Cue>, Png2tError> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => {
+            return Err(Png2tError::io(
+                format!("Unable to read subtitles from {}", path.display()),
+                e,
+            ))
+        }
+    };
+
+    parse_srt(&text).map_err(|e| Png2tError::decode(path.display().to_string(), e))
+}
+
+/// Parses SubRip subtitles into cues, sorted by when they start.
+///
+/// Each cue is an optional number, a line like `00:01:02,500 --> 00:01:04,000`, then its text, with blank lines between
+/// cues. Formatting tags such as `<i>` are stripped, since there's no way to show them.
+///
+/// # Errors
+/// Fails if a cue's timing line is missing or malformed, saying which line it was on.
+pub(crate) fn parse_srt(text: &str) -> Result<Vec<Cue>, String> {
+    let mut cues = Vec::new();
+    let mut lines = text
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .enumerate()
+        .peekable();
+
+    while let Some((idx, line)) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        // The number is only there for people reading the file, so it's skipped over if present
+        let (idx, timing) = match line.chars().all(|c| c.is_ascii_digit()) {
+            true => match lines.next() {
+                Some((idx, timing)) => (idx, timing.trim()),
+                None => return Err(format!("cue {} on line {} has no timing", line, idx + 1)),
+            },
+            false => (idx, line),
+        };
+
+        let Some((start, end)) = timing.split_once("-->") else {
+            return Err(format!(
+                "expected a timing like 00:00:01,000 --> 00:00:02,000 on line {}, found '{}'",
+                idx + 1,
+                timing
+            ));
+        };
+        // Anything after the end time (such as positioning) is ignored
+        let end = end.split_whitespace().next().unwrap_or_default();
+        let (start, end) = (parse_timestamp(start.trim()), parse_timestamp(end));
+        let (Some(start), Some(end)) = (start, end) else {
+            return Err(format!("invalid timing on line {}: '{}'", idx + 1, timing));
+        };
+
+        let mut text = Vec::new();
+        while let Some((_, line)) = lines.next_if(|(_, l)| !l.trim().is_empty()) {
+            text.push(strip_tags(line.trim()));
+        }
+
+        cues.push(Cue {
+            start,
+            end,
+            lines: text,
+        });
+    }
+
+    cues.sort_by_key(|cue| cue.start);
+    Ok(cues)
+}
+
+/// Finds the cue showing at `time`, if any. Where cues overlap, the one that started first wins.
+pub(crate) fn active_cue(cues: &[Cue], time: Duration) -> Option<usize> {
+    cues.iter()
+        .position(|cue| cue.start <= time && time < cue.end)
+}
+
+/// Parses a SubRip timestamp, `HH:MM:SS,mmm`. A `.` is also accepted before the milliseconds.
+fn parse_timestamp(s: &str) -> Option<Duration> {
+    let (hms, millis) = s.split_once([',', '.'])?;
+    let mut parts = hms.split(':').map(|p| p.parse::<u64>().ok());
+    let (h, m, sec) = (parts.next()??, parts.next()??, parts.next()??);
+    if parts.next().is_some() || m >= 60 || sec >= 60 {
+        return None;
+    }
+
+    let millis: u64 = millis.parse().ok()?;
+    Some(Duration::from_millis(
+        ((h * 60 + m) * 60 + sec) * 1000 + millis,
+    ))
+}
+
+/// Removes anything between `<` and `>` from `line`.
+fn strip_tags(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut in_tag = false;
+    for c in line.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srt_cues_parse_and_look_up_by_time() {
+        let srt = "\u{feff}2\r\n00:00:03,500 --> 00:00:05,000 X1:0\r\n<i>Second</i>\r\n\r\n\
+                   1\r\n00:00:01,000 --> 00:00:02,250\r\nFirst\r\ntwo lines\r\n";
+        let cues = parse_srt(srt).unwrap();
+
+        assert_eq!(
+            cues,
+            [
+                Cue {
+                    start: Duration::from_millis(1000),
+                    end: Duration::from_millis(2250),
+                    lines: vec![String::from("First"), String::from("two lines")],
+                },
+                Cue {
+                    start: Duration::from_millis(3500),
+                    end: Duration::from_millis(5000),
+                    lines: vec![String::from("Second")],
+                },
+            ]
+        );
+        assert_eq!(active_cue(&cues, Duration::from_millis(999)), None);
+        assert_eq!(active_cue(&cues, Duration::from_millis(1000)), Some(0));
+        assert_eq!(active_cue(&cues, Duration::from_millis(2250)), None);
+        assert_eq!(active_cue(&cues, Duration::from_millis(4000)), Some(1));
+
+        assert!(parse_srt("1\n00:00:01 --> 00:00:02\nNo millis\n").is_err());
+        assert!(parse_srt("1\nHello\n").is_err());
+    }
+}
@@ -0,0 +1,50 @@
+use std::{io::stdout, thread};
+
+use crossterm::{
+    cursor::Show,
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, is_raw_mode_enabled},
+};
+use signal_hook::{consts::SIGTSTP, iterator::Signals, low_level};
+
+use crate::error::Png2tError;
+
+/// Makes `SIGTSTP` (as sent by `kill -TSTP`, or Ctrl-Z outside of playback) suspend png2t cleanly. See `suspend`.
+///
+/// While a video is playing the terminal is in raw mode, where Ctrl-Z arrives as a key press rather than a signal,
+/// so playback calls `suspend` itself when it sees one.
+///
+/// # Errors
+/// Fails if the signal handler can't be registered.
+pub fn handle_suspend() -> Result<(), Png2tError> {
+    let mut signals = match Signals::new([SIGTSTP]) {
+        Ok(s) => s,
+        Err(e) => return Err(Png2tError::io("Unable to listen for suspend signals", e)),
+    };
+
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            suspend();
+        }
+    });
+
+    Ok(())
+}
+
+/// Stops png2t the way Ctrl-Z normally would, returning the terminal to normal first.
+///
+/// Raw mode is turned back on when png2t is resumed with `fg`, if it was on to begin with.
+pub(crate) fn suspend() {
+    let raw = is_raw_mode_enabled().unwrap_or(false);
+    if raw {
+        let _ = disable_raw_mode();
+    }
+    let _ = execute!(stdout(), Show);
+
+    // Stops the whole process, and returns once it's continued
+    let _ = low_level::emulate_default_handler(SIGTSTP);
+
+    if raw {
+        let _ = enable_raw_mode();
+    }
+}
@@ -0,0 +1,169 @@
+use std::str::FromStr;
+
+use image::{DynamicImage, Rgba32FImage};
+
+use crate::Image;
+
+/// How HDR frames have their highlights brought down into the range a terminal can show.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Tonemap {
+    /// Extended Reinhard, with each frame's brightest pixel as the white point. Nothing clips, but bright
+    /// frames can look flat.
+    #[default]
+    Reinhard,
+    /// The filmic curve from ACES (as fitted by Krzysztof Narkowicz), which has more contrast and lets the very
+    /// brightest highlights roll off into white.
+    Aces,
+}
+
+impl FromStr for Tonemap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "reinhard" => Ok(Tonemap::Reinhard),
+            "aces" => Ok(Tonemap::Aces),
+            _ => Err(format!(
+                "unknown tone-mapping operator '{}': expected one of aces, reinhard",
+                s
+            )),
+        }
+    }
+}
+
+/// Converts a decoded frame to 8-bit sRGB for rendering.
+///
+/// 8-bit and 16-bit sources are converted directly. Float sources (i.e. the EXR intermediates) hold
+/// linear light that may go well past 1.0, so they're tone-mapped with `op` and gamma-encoded rather than clipped.
+pub fn to_rgba8(img: DynamicImage, op: Tonemap) -> Image {
+    match img {
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => {
+            tonemap(&img.into_rgba32f(), op)
+        }
+        other => other.into_rgba8(),
+    }
+}
+
+/// Tone-maps a linear HDR frame into 8-bit sRGB with `op`.
+///
+/// Either curve is applied to luminance, and the color scaled to match, which keeps hues intact.
+/// Reinhard leaves frames that never exceed 1.0 as they are, since its curve approaches the identity as their peak
+/// does. ACES has a fixed curve that doesn't, so it's applied to every frame alike, or the brightness would jump
+/// whenever a video's peak crossed 1.0.
+fn tonemap(img: &Rgba32FImage, op: Tonemap) -> Image {
+    let white = img.pixels().map(|p| luminance(p.0)).fold(1.0f32, f32::max);
+
+    let mut out = Image::new(img.width(), img.height());
+    for (src, dst) in img.pixels().zip(out.pixels_mut()) {
+        let [r, g, b, a] = src.0;
+        let l = luminance(src.0);
+
+        // Scale the color so its luminance follows the curve, which keeps hues intact.
+        // Reinhard is skipped outright for SDR frames, where its curve is the identity anyway.
+        let ratio = match op {
+            _ if l <= 0.0 => 0.0,
+            Tonemap::Reinhard if white <= 1.0 => 1.0,
+            Tonemap::Reinhard => (1.0 + l / (white * white)) / (1.0 + l),
+            Tonemap::Aces => aces(l) / l,
+        };
+
+        dst.0 = [
+            encode_srgb(r * ratio),
+            encode_srgb(g * ratio),
+            encode_srgb(b * ratio),
+            (a.clamp(0.0, 1.0) * 255.0).round() as u8,
+        ];
+    }
+
+    out
+}
+
+/// Narkowicz's fit of the ACES filmic curve, clamped to 1.0.
+fn aces(x: f32) -> f32 {
+    (x * (2.51 * x + 0.03) / (x * (2.43 * x + 0.59) + 0.14)).min(1.0)
+}
+
+/// Relative luminance of a linear color, per Rec. 709.
+fn luminance([r, g, b, _]: [f32; 4]) -> f32 {
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+/// Applies the sRGB transfer function to a linear channel value and quantizes it to 8 bits.
+pub(crate) fn encode_srgb(c: f32) -> u8 {
+    let c = c.clamp(0.0, 1.0);
+    let encoded = match c <= 0.0031308 {
+        true => 12.92 * c,
+        false => 1.055 * c.powf(1.0 / 2.4) - 0.055,
+    };
+    (encoded * 255.0).round() as u8
+}
+
+/// Undoes the sRGB transfer function on an 8-bit channel value, giving linear light from 0 to 1.
+pub(crate) fn decode_srgb(c: u8) -> f32 {
+    let c = c as f32 / 255.0;
+    match c <= 0.04045 {
+        true => c / 12.92,
+        false => ((c + 0.055) / 1.055).powf(2.4),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn tonemap_keeps_sdr_range_and_compresses_highlights() {
+        let img = Rgba32FImage::from_fn(3, 1, |x, _| match x {
+            0 => Rgba([0.0, 0.0, 0.0, 1.0]),
+            1 => Rgba([0.5, 0.5, 0.5, 1.0]),
+            _ => Rgba([4.0, 4.0, 4.0, 1.0]),
+        });
+        let out = tonemap(&img, Tonemap::Reinhard);
+
+        assert_eq!(out.get_pixel(0, 0).0, [0, 0, 0, 255]);
+        // The highlight is the white point, so it lands exactly on white instead of clipping everything above 1.0
+        assert_eq!(out.get_pixel(2, 0).0, [255, 255, 255, 255]);
+        // Midtones are compressed towards black to make room, but stay distinct
+        let mid = out.get_pixel(1, 0).0[0];
+        assert!(mid > 0 && mid < encode_srgb(0.5));
+    }
+
+    #[test]
+    fn aces_rolls_highlights_off_into_white() {
+        let img = Rgba32FImage::from_fn(3, 1, |x, _| match x {
+            0 => Rgba([0.18, 0.18, 0.18, 1.0]),
+            1 => Rgba([0.8, 0.6, 0.4, 1.0]),
+            _ => Rgba([16.0, 16.0, 16.0, 1.0]),
+        });
+        let out = tonemap(&img, Tonemap::Aces);
+
+        assert_eq!(out.get_pixel(2, 0).0, [255, 255, 255, 255]);
+        // Midtones aren't clipped, and keep their hue
+        let [r, g, b, _] = out.get_pixel(1, 0).0;
+        assert!(r < 255 && r > g && g > b);
+    }
+
+    #[test]
+    fn aces_is_steady_as_the_peak_crosses_one() {
+        let frame = |peak| {
+            Rgba32FImage::from_fn(2, 1, |x, _| match x {
+                0 => Rgba([0.18, 0.18, 0.18, 1.0]),
+                _ => Rgba([peak, peak, peak, 1.0]),
+            })
+        };
+        let (below, above) = (
+            tonemap(&frame(0.99), Tonemap::Aces),
+            tonemap(&frame(1.01), Tonemap::Aces),
+        );
+
+        assert_eq!(below.get_pixel(0, 0), above.get_pixel(0, 0));
+        // The peaks themselves stay next to each other too
+        assert!(below.get_pixel(1, 0).0[0].abs_diff(above.get_pixel(1, 0).0[0]) <= 1);
+    }
+
+    #[test]
+    fn srgb_decodes_back_to_itself() {
+        assert!((0..=255).all(|c| encode_srgb(decode_srgb(c)) == c));
+    }
+}
@@ -0,0 +1,1159 @@
+use std::str::FromStr;
+
+use image::{
+    imageops::{
+        blur, flip_horizontal_in_place, flip_vertical_in_place, replace, resize, unsharpen,
+        FilterType,
+    },
+    Rgba,
+};
+use rayon::{prelude::*, ThreadPoolBuilder};
+
+use crate::{error::Png2tError, lut::Lut, palette::Palette, render::luminance, Image};
+
+/// Filter used to resize frames. Nearest-neighbor keeps pixel art crisp and is by far the fastest.
+pub(crate) const FILTER: FilterType = FilterType::Nearest;
+
+/// Largest strength accepted for `sharpen` and `blur`. Frames are usually small enough that anything past this
+/// is indistinguishable, and it keeps a typo from taking forever to apply.
+const MAX_FILTER_SIGMA: f32 = 20.0;
+
+/// How frames are made to fill an exact `size` whose aspect ratio doesn't match theirs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Fit {
+    /// Stretch or squash frames to fill it exactly.
+    #[default]
+    Stretch,
+    /// Scale frames to fit inside it, and pad out the rest with transparency (letterboxing).
+    Contain,
+    /// Scale frames to fill it, and crop off whatever hangs over the edges.
+    Cover,
+}
+
+impl FromStr for Fit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "stretch" => Ok(Fit::Stretch),
+            "contain" => Ok(Fit::Contain),
+            "cover" => Ok(Fit::Cover),
+            _ => Err(format!(
+                "unknown fit '{}': expected one of contain, cover, stretch",
+                s
+            )),
+        }
+    }
+}
+
+/// Where `TransformConfig::threshold` splits black from white.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Threshold {
+    /// Pixels at least this bright (from 0 to 255) turn white.
+    Level(u8),
+    /// Pick the level for each frame with Otsu's method, which finds the split that best separates its darks from its lights.
+    Auto,
+}
+
+/// Options for reshaping and recoloring frames before they're rendered.
+///
+/// The default leaves colors alone and shrinks frames to fit in 64x64 pixels.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TransformConfig {
+    /// Flip frames horizontally.
+    pub flip_h: bool,
+    /// Flip frames vertically.
+    pub flip_v: bool,
+    /// Resize frames to exactly these dimensions.
+    pub size: Option<(u32, u32)>,
+    /// How frames fill `size` when their aspect ratio doesn't match it. Padding is left transparent, so it shows
+    /// the background color if one is set when rendering.
+    pub fit: Fit,
+    /// Resize frames to be this many terminal columns (and so pixels) wide, keeping their aspect ratio. Ignored if `size` is set.
+    pub cols: Option<u32>,
+    /// Scale the width and height of frames by these factors, after any other resizing.
+    /// A negative factor also flips frames along that axis, as `flip_h` or `flip_v` would.
+    pub scale: Option<(f32, f32)>,
+    /// Keep the source dimensions instead of automatically shrinking frames.
+    pub preserve_dims: bool,
+    /// Height of a terminal cell divided by its width. Each cell holds two pixels stacked vertically, so at the
+    /// usual 2.0 pixels come out square and nothing needs correcting. Doesn't apply to `size`, which is always exact.
+    pub cell_aspect: Option<f32>,
+    /// Snap every pixel to the nearest color in this palette.
+    pub palette: Option<Palette>,
+    /// Average every block of this many pixels across (in the source, before resizing) into one color, for a mosaic look.
+    pub pixelate: Option<u32>,
+    /// Replace frames with their edges, drawn in white on black, for a sketch-like look. Applied after resizing.
+    pub edges: bool,
+    /// Rotate the hue of every pixel by this many degrees.
+    pub hue: Option<f32>,
+    /// Multiply the saturation of every pixel by this factor. 0 makes frames grayscale.
+    pub saturation: Option<f32>,
+    /// Reduce every channel to this many evenly spaced levels, from 2 to 256.
+    pub posterize: Option<u16>,
+    /// Turn every pixel pure black or white depending on its brightness, after any other color adjustments.
+    pub threshold: Option<Threshold>,
+    /// Sharpen frames with an unsharp mask of this radius, after resizing.
+    pub sharpen: Option<f32>,
+    /// Blur frames with a gaussian of this sigma, after resizing.
+    pub blur: Option<f32>,
+    /// Tile frames 2x2 with mirrored copies of themselves, for a kaleidoscope effect. Each copy is flipped towards the
+    /// original, so they meet along its edges. This doubles both dimensions, though the result still has to fit in
+    /// `max_width`.
+    pub mirror: bool,
+    /// Pad frames out to a square, centered, after everything else. The padding is transparent, so it shows the
+    /// background color if one is set when rendering.
+    pub square: bool,
+    /// Never let frames end up wider than this, shrinking them (keeping their aspect ratio) with a warning if they would.
+    /// Meant for the terminal's width, since anything wider wraps and scrambles the picture.
+    pub max_width: Option<u32>,
+    /// Resize with SIMD instructions through `fast_image_resize`, when png2t is built with the `fast-resize` feature.
+    /// Without the feature, or when off, frames are resized by `image` instead.
+    pub simd: bool,
+    /// Most threads to transform frames on at once, or `None` for one per CPU.
+    pub threads: Option<usize>,
+    /// Log how the output dimensions were worked out to stderr.
+    pub verbose: bool,
+}
+
+/// Parses a size in the format `WIDTHxHEIGHT`, such as `64x32`.
+///
+/// # Errors
+/// Fails unless there are exactly two components, both of which are positive integers.
+pub fn parse_size(s: &str) -> Result<(u32, u32), String> {
+    let parts: Vec<&str> = s.split('x').collect();
+    if parts.len() != 2 {
+        return Err(format!(
+            "expected 2 dimensions in the format WIDTHxHEIGHT, found {}",
+            parts.len()
+        ));
+    }
+
+    let mut dims = [0u32; 2];
+    for (dim, part) in dims.iter_mut().zip(parts) {
+        *dim = match part.trim().parse() {
+            Ok(0) => return Err(String::from("dimensions must be greater than 0")),
+            Ok(n) => n,
+            Err(_) => return Err(format!("'{}' is not a valid dimension", part)),
+        };
+    }
+
+    Ok((dims[0], dims[1]))
+}
+
+/// Parses a scale factor, either one number for both the width and height such as `2`, or `XxY` such as `2x1.5`.
+///
+/// # Errors
+/// Fails unless there are one or two components, each of which is a nonzero, finite number.
+pub fn parse_scale(s: &str) -> Result<(f32, f32), String> {
+    let parts: Vec<&str> = s.split('x').collect();
+    let mut factors = [0f32; 2];
+    for (factor, part) in factors.iter_mut().zip(&parts) {
+        *factor = match part.trim().parse() {
+            Ok(n) if n == 0.0 || !f32::is_finite(n) => {
+                return Err(String::from("factors must be nonzero numbers"))
+            }
+            Ok(n) => n,
+            Err(_) => return Err(format!("'{}' is not a valid factor", part)),
+        };
+    }
+
+    match parts.len() {
+        1 => Ok((factors[0], factors[0])),
+        2 => Ok((factors[0], factors[1])),
+        n => Err(format!(
+            "expected a factor, or 2 in the format XxY, found {}",
+            n
+        )),
+    }
+}
+
+/// Works out the largest size `dims` can be shrunk to while fitting inside `space`, keeping its aspect ratio.
+///
+/// # Returns
+/// `None` if it already fits and doesn't need shrinking at all.
+pub(crate) fn fit_within(dims: (u32, u32), space: (u32, u32)) -> Option<(u32, u32)> {
+    let (w, h) = dims;
+    let (max_w, max_h) = space;
+    if w <= max_w && h <= max_h {
+        return None;
+    }
+
+    // Whichever side is further over the limit decides how much to shrink by
+    let factor = f64::min(max_w as f64 / w as f64, max_h as f64 / h as f64);
+    Some((
+        ((w as f64 * factor) as u32).max(1),
+        ((h as f64 * factor) as u32).max(1),
+    ))
+}
+
+/// Resizes `frame` to exactly `dims`, dealing with any difference in aspect ratio according to `fit`.
+/// `simd` picks how, as in `TransformConfig`.
+fn resize_to(frame: &Image, dims: (u32, u32), fit: Fit, simd: bool) -> Image {
+    let (w, h) = dims;
+    let (sw, sh) = frame.dimensions();
+    let (fx, fy) = (w as f64 / sw as f64, h as f64 / sh as f64);
+    let factor = match fit {
+        Fit::Stretch => return resize_exact(frame, w, h, simd),
+        Fit::Contain => fx.min(fy),
+        Fit::Cover => fx.max(fy),
+    };
+
+    let (iw, ih) = (
+        ((sw as f64 * factor).round() as u32).max(1),
+        ((sh as f64 * factor).round() as u32).max(1),
+    );
+    let scaled = resize_exact(frame, iw, ih, simd);
+
+    // Center it in the box. The offset is negative along whichever side hangs over the edge, which crops it.
+    let mut out = Image::new(w, h);
+    replace(
+        &mut out,
+        &scaled,
+        (w as i64 - iw as i64) / 2,
+        (h as i64 - ih as i64) / 2,
+    );
+    out
+}
+
+/// Resizes `frame` to `w`x`h` with `FILTER`, using SIMD if `simd` is set and it's built in.
+fn resize_exact(frame: &Image, w: u32, h: u32, simd: bool) -> Image {
+    #[cfg(feature = "fast-resize")]
+    if let Some(resized) = simd.then(|| fast_resize(frame, w, h)).flatten() {
+        return resized;
+    }
+    #[cfg(not(feature = "fast-resize"))]
+    let _ = simd;
+
+    resize(frame, w, h, FILTER)
+}
+
+/// Resizes `frame` to `w`x`h` with `fast_image_resize`, which picks the best SIMD instructions the CPU has.
+///
+/// # Returns
+/// `None` if it couldn't be done, in which case `image`'s resize can be used instead.
+#[cfg(feature = "fast-resize")]
+fn fast_resize(frame: &Image, w: u32, h: u32) -> Option<Image> {
+    use fast_image_resize as fr;
+    use std::num::NonZeroU32;
+
+    let src = fr::Image::from_vec_u8(
+        NonZeroU32::new(frame.width())?,
+        NonZeroU32::new(frame.height())?,
+        frame.as_raw().clone(),
+        fr::PixelType::U8x4,
+    )
+    .ok()?;
+    let mut dst = fr::Image::new(
+        NonZeroU32::new(w)?,
+        NonZeroU32::new(h)?,
+        fr::PixelType::U8x4,
+    );
+
+    // Nearest-neighbor, to match `FILTER`. It never blends pixels, so there's no need to premultiply alpha either.
+    fr::Resizer::new(fr::ResizeAlg::Nearest)
+        .resize(&src.view(), &mut dst.view_mut())
+        .ok()?;
+    Image::from_raw(w, h, dst.into_vec())
+}
+
+/// Transform every frame based on `config`
+///
+/// All frames are resized to the same dimensions, which are calculated from the first frame.
+/// Frames are then transformed in parallel, on up to `config.threads` threads.
+/// This function has potential to be the slowest in the rendering process if done with too many flags - be careful in here
+///
+/// # Errors
+/// Fails if `config.scale` is zero or not finite, or `config.cell_aspect` is not a positive, finite number, if the frames would end up with no pixels,
+/// or if the threads can't be started.
+pub fn transform_frames(frames: &mut [Image], config: &TransformConfig) -> Result<(), Png2tError> {
+    let (mut nwidth, mut nheight) = match frames.first() {
+        Some(frame) => frame.dimensions(),
+        None => return Ok(()),
+    };
+    let (swidth, sheight) = (nwidth, nheight);
+
+    // Every other way of sizing already keeps the aspect ratio (or changes it on purpose), so fitting only applies to `size`
+    let fit = match config.size {
+        Some(_) => config.fit,
+        None => Fit::Stretch,
+    };
+
+    // The following block calculates the final image size. Multiple factors influence it so it's best to calculate it once.
+    // This means we can't support dynamically resizing .mp4s and such, but I think that's okay... (sorry Discord trolls)
+    if let Some((w, h)) = config.size {
+        nwidth = w;
+        nheight = h;
+    } else if let Some(cols) = config.cols {
+        // One pixel per column, with the height following along. Each row of characters then holds two pixels.
+        nheight = ((cols as f64 * (nheight as f64 / nwidth as f64)) as u32).max(1);
+        nwidth = cols;
+    } else if !config.preserve_dims {
+        // Set the longest side to be 64px, with the shorter side scaling down proportionally to preserve aspect ratio
+        (nwidth, nheight) = match nwidth > nheight {
+            true => (
+                64,
+                ((64f64 * (nheight as f64 / nwidth as f64)) as u32).max(1),
+            ),
+            false => (
+                ((64f64 * (nwidth as f64 / nheight as f64)) as u32).max(1),
+                64,
+            ),
+        };
+    }
+
+    // Stretch or squash the height so that pixels drawn at half a cell tall come out square.
+    // `--size` asks for exact dimensions, so it's left alone.
+    if let (Some(aspect), None) = (config.cell_aspect, config.size) {
+        if !aspect.is_finite() || aspect <= 0.0 {
+            return Err(Png2tError::InvalidArgs(format!(
+                "Invalid ratio supplied to --cell-aspect: {} (must be a positive number)",
+                aspect
+            )));
+        }
+
+        nheight = ((nheight as f32 * 2.0 / aspect).round() as u32).max(1);
+    }
+
+    // A negative factor flips that axis as well, which undoes a flip that was asked for the usual way
+    let (mut flip_h, mut flip_v) = (config.flip_h, config.flip_v);
+    if let Some((x, y)) = config.scale {
+        for scale in [x, y] {
+            if !scale.is_finite() || scale == 0.0 {
+                return Err(Png2tError::InvalidArgs(format!(
+                    "Invalid factor supplied to --scale: {} (must be a nonzero number)",
+                    scale
+                )));
+            }
+        }
+
+        // Tiny factors would otherwise round down to nothing
+        nwidth = ((nwidth as f32 * x.abs()) as u32).max(1);
+        nheight = ((nheight as f32 * y.abs()) as u32).max(1);
+        flip_h ^= x < 0.0;
+        flip_v ^= y < 0.0;
+    }
+
+    // Mirroring tiles four copies of each frame, so everything from here on is about the whole tiling
+    if config.mirror {
+        nwidth *= 2;
+        nheight *= 2;
+    }
+
+    // Whatever asked for them, frames that are too wide would wrap onto the next line.
+    // Padding to a square makes them as wide as they are tall, so then the height has to fit too.
+    if let Some(max) = config.max_width {
+        let max = max.max(1);
+        let space = match config.square {
+            true => (max, max),
+            false => (max, u32::MAX),
+        };
+        if let Some((w, h)) = fit_within((nwidth, nheight), space) {
+            // Only a square's padding can make the height the problem, and then it's padded out to the height
+            let reason = match nwidth > max {
+                true => format!("which is wider than the terminal's {} columns", max),
+                false => format!(
+                    "which --square pads out to {} columns, wider than the terminal's {}",
+                    nheight, max
+                ),
+            };
+            eprintln!(
+                "Warning: frames would be {}x{}, {}. Shrinking them to {}x{}.",
+                nwidth, nheight, reason, w, h
+            );
+            (nwidth, nheight) = (w, h);
+        }
+    }
+
+    if config.pixelate == Some(0) {
+        return Err(Png2tError::InvalidArgs(String::from(
+            "Invalid block size supplied to --pixelate: must be at least 1",
+        )));
+    }
+
+    if let Some(hue) = config.hue {
+        if !hue.is_finite() {
+            return Err(Png2tError::InvalidArgs(format!(
+                "Invalid angle supplied to --hue: {}",
+                hue
+            )));
+        }
+    }
+
+    if let Some(saturation) = config.saturation {
+        if !saturation.is_finite() || saturation < 0.0 {
+            return Err(Png2tError::InvalidArgs(format!(
+                "Invalid factor supplied to --saturation: {} (must be 0 or more)",
+                saturation
+            )));
+        }
+    }
+
+    if let Some(levels) = config.posterize {
+        if !(2..=256).contains(&levels) {
+            return Err(Png2tError::InvalidArgs(format!(
+                "Invalid number of levels supplied to --posterize: {} (must be from 2 to 256)",
+                levels
+            )));
+        }
+    }
+
+    for (value, flag) in [(config.sharpen, "--sharpen"), (config.blur, "--blur")] {
+        if let Some(v) = value {
+            if !v.is_finite() || v <= 0.0 || v > MAX_FILTER_SIGMA {
+                return Err(Png2tError::InvalidArgs(format!(
+                    "Invalid amount supplied to {}: {} (must be above 0 and at most {})",
+                    flag, v, MAX_FILTER_SIGMA
+                )));
+            }
+        }
+    }
+
+    // Safety net: everything downstream assumes there's at least one pixel to draw
+    if nwidth == 0 || nheight == 0 {
+        let culprit = match (config.size, config.cols) {
+            (Some(_), _) => "--size",
+            (None, Some(_)) => "--cols",
+            (None, None) => "the source dimensions",
+        };
+        return Err(Png2tError::InvalidArgs(format!(
+            "Frames would be resized to {}x{}, which can't be rendered. Check {}.",
+            nwidth, nheight, culprit
+        )));
+    }
+
+    // What each frame is resized to before being mirrored, if it is. Odd sizes lose a pixel to fit four copies evenly.
+    let tile = match config.mirror {
+        true => ((nwidth / 2).max(1), (nheight / 2).max(1)),
+        false => (nwidth, nheight),
+    };
+    if config.mirror {
+        (nwidth, nheight) = (tile.0 * 2, tile.1 * 2);
+    }
+
+    if config.verbose {
+        eprintln!("Source dimensions: {}x{}", swidth, sheight);
+        eprintln!(
+            "Sized by: {}{}{}",
+            match (config.size, config.cols, config.preserve_dims) {
+                (Some(_), _, _) => "--size",
+                (None, Some(_), _) => "--cols",
+                (None, None, true) => "--preserve-dims",
+                (None, None, false) => "auto-fit to 64px",
+            },
+            match (config.cell_aspect, config.size) {
+                (Some(_), None) => ", then --cell-aspect",
+                _ => "",
+            },
+            match config.scale {
+                Some(_) => ", then --scale",
+                None => "",
+            }
+        );
+        if config.size.is_some() {
+            eprintln!("Fit: {:?}", fit);
+        }
+        eprintln!(
+            "Resize filter: {:?}{}",
+            FILTER,
+            match cfg!(feature = "fast-resize") && config.simd {
+                true => " (SIMD)",
+                false => "",
+            }
+        );
+        eprintln!("Output dimensions: {}x{}", nwidth, nheight);
+        if config.mirror {
+            eprintln!("Mirrored from: {}x{}", tile.0, tile.1);
+        }
+        if config.square {
+            let side = nwidth.max(nheight);
+            eprintln!("Padded to: {}x{}", side, side);
+        }
+    }
+
+    // Every operation that works on channels one at a time, composed into one table up front
+    let mut points = Lut::identity();
+    if let Some(levels) = config.posterize {
+        points = points.then(|c| posterize(c, levels));
+    }
+
+    // Each frame is transformed on its own, so they can be shared out between threads
+    let transform = |frame: &mut Image| {
+        // Done at the source resolution so the blocks stay the same size whatever the output ends up as
+        if let Some(block) = config.pixelate.filter(|&b| b > 1) {
+            pixelate(frame, block);
+        }
+
+        *frame = resize_to(frame, tile, fit, config.simd);
+
+        // Filtering is done at the final size, so it's as cheap as it can be and its strength is relative to what's shown
+        if let Some(sigma) = config.blur {
+            *frame = blur(frame, sigma);
+        }
+        if let Some(sigma) = config.sharpen {
+            *frame = unsharpen(frame, sigma, 0);
+        }
+        if config.edges {
+            *frame = sobel(frame);
+        }
+
+        if config.hue.is_some() || config.saturation.is_some() {
+            for pixel in frame.chunks_exact_mut(4) {
+                let graded = adjust_hsl(
+                    [pixel[0], pixel[1], pixel[2]],
+                    config.hue.unwrap_or(0.0),
+                    config.saturation.unwrap_or(1.0),
+                );
+                pixel[..3].copy_from_slice(&graded);
+            }
+        }
+
+        if !points.is_identity() {
+            points.apply(frame);
+        }
+
+        // An automatic threshold depends on how the frame looks after everything above, so it takes a second pass
+        let level = config.threshold.map(|t| match t {
+            Threshold::Level(level) => level,
+            Threshold::Auto => otsu_level(frame),
+        });
+        if level.is_some() || config.palette.is_some() {
+            for pixel in frame.chunks_exact_mut(4) {
+                if let Some(level) = level {
+                    let v = match luma([pixel[0], pixel[1], pixel[2]]) >= level {
+                        true => u8::MAX,
+                        false => 0,
+                    };
+                    pixel[..3].fill(v);
+                }
+
+                if let Some(palette) = config.palette {
+                    let snapped = palette.nearest([pixel[0], pixel[1], pixel[2]]);
+                    pixel[..3].copy_from_slice(&snapped);
+                }
+            }
+        }
+
+        if flip_h {
+            flip_horizontal_in_place(frame)
+        }
+
+        if flip_v {
+            flip_vertical_in_place(frame)
+        }
+
+        if config.mirror {
+            *frame = mirror(frame);
+        }
+
+        if config.square {
+            *frame = pad_square(frame);
+        }
+    };
+
+    match config.threads {
+        Some(threads) => match ThreadPoolBuilder::new().num_threads(threads).build() {
+            Ok(pool) => pool.install(|| frames.par_iter_mut().for_each(transform)),
+            Err(e) => {
+                return Err(Png2tError::InvalidArgs(format!(
+                    "Unable to start {} threads for --threads: {}",
+                    threads, e
+                )))
+            }
+        },
+        None => frames.par_iter_mut().for_each(transform),
+    }
+
+    Ok(())
+}
+
+/// Tiles `frame` 2x2, with it in the top left and copies flipped horizontally, vertically and both around it.
+fn mirror(frame: &Image) -> Image {
+    let (w, h) = frame.dimensions();
+    let mut out = Image::new(w * 2, h * 2);
+    replace(&mut out, frame, 0, 0);
+
+    // Each flip builds on the last, going clockwise around the quadrants
+    let mut flipped = frame.clone();
+    flip_horizontal_in_place(&mut flipped);
+    replace(&mut out, &flipped, w as i64, 0);
+    flip_vertical_in_place(&mut flipped);
+    replace(&mut out, &flipped, w as i64, h as i64);
+    flip_horizontal_in_place(&mut flipped);
+    replace(&mut out, &flipped, 0, h as i64);
+    out
+}
+
+/// Centers `frame` in a transparent square as big as its longer side.
+fn pad_square(frame: &Image) -> Image {
+    let (w, h) = frame.dimensions();
+    let side = w.max(h);
+
+    let mut out = Image::new(side, side);
+    replace(
+        &mut out,
+        frame,
+        ((side - w) / 2) as i64,
+        ((side - h) / 2) as i64,
+    );
+    out
+}
+
+/// Fills every `block`x`block` square of `frame` with its average color.
+///
+/// Squares along the right and bottom edges are cut short if `block` doesn't divide the frame evenly, and are averaged over
+/// whatever pixels they do cover. Colors are weighted by alpha, so transparent pixels don't darken their neighbors.
+fn pixelate(frame: &mut Image, block: u32) {
+    let (w, h) = frame.dimensions();
+    for by in (0..h).step_by(block as usize) {
+        for bx in (0..w).step_by(block as usize) {
+            let (xs, ys) = (bx..(bx + block).min(w), by..(by + block).min(h));
+
+            let mut sum = [0u64; 4];
+            for y in ys.clone() {
+                for x in xs.clone() {
+                    let p = frame.get_pixel(x, y);
+                    let a = p[3] as u64;
+                    for c in 0..3 {
+                        sum[c] += p[c] as u64 * a;
+                    }
+                    sum[3] += a;
+                }
+            }
+
+            let count = (xs.len() * ys.len()) as u64;
+            let avg = match sum[3] {
+                0 => Rgba([0, 0, 0, 0]),
+                a => Rgba([
+                    (sum[0] / a) as u8,
+                    (sum[1] / a) as u8,
+                    (sum[2] / a) as u8,
+                    (a / count) as u8,
+                ]),
+            };
+            for y in ys.clone() {
+                for x in xs.clone() {
+                    frame.put_pixel(x, y, avg);
+                }
+            }
+        }
+    }
+}
+
+/// Draws how sharply the brightness of `frame` changes at every pixel, using the Sobel operator.
+///
+/// The result is grayscale, brightest along the strongest edges, and keeps the original alpha. Pixels past the borders
+/// count as copies of the nearest one inside, so the borders themselves don't come out as edges.
+fn sobel(frame: &Image) -> Image {
+    let (w, h) = frame.dimensions();
+    let lum: Vec<f32> = frame
+        .pixels()
+        .map(|p| luminance([p[0], p[1], p[2]]))
+        .collect();
+    let at = |x: i64, y: i64| {
+        let (x, y) = (x.clamp(0, w as i64 - 1), y.clamp(0, h as i64 - 1));
+        lum[(y * w as i64 + x) as usize]
+    };
+
+    Image::from_fn(w, h, |px, py| {
+        let (x, y) = (px as i64, py as i64);
+        let gx = at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+            - at(x - 1, y - 1)
+            - 2.0 * at(x - 1, y)
+            - at(x - 1, y + 1);
+        let gy = at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+            - at(x - 1, y - 1)
+            - 2.0 * at(x, y - 1)
+            - at(x + 1, y - 1);
+
+        // A full black to white step along one axis comes to 4, which is as bright as it gets
+        let v = ((gx * gx + gy * gy).sqrt() / 4.0).min(1.0) * 255.0;
+        let v = v.round() as u8;
+        Rgba([v, v, v, frame.get_pixel(px, py)[3]])
+    })
+}
+
+/// Brightness of a color from 0 to 255. See `render::luminance`.
+#[inline]
+fn luma(rgb: [u8; 3]) -> u8 {
+    (luminance(rgb) * 255.0).round() as u8
+}
+
+/// Works out the threshold level that best splits `frame` into dark and light pixels, using Otsu's method.
+///
+/// Every possible level is tried, picking the one that maximizes the variance between the two groups.
+/// Transparent pixels aren't drawn, so they're left out.
+fn otsu_level(frame: &Image) -> u8 {
+    let mut hist = [0u64; 256];
+    for p in frame.pixels().filter(|p| p[3] > 0) {
+        hist[luma([p[0], p[1], p[2]]) as usize] += 1;
+    }
+
+    let total: u64 = hist.iter().sum();
+    let sum: f64 = hist
+        .iter()
+        .enumerate()
+        .map(|(i, &n)| i as f64 * n as f64)
+        .sum();
+
+    // Running totals for the dark group, i.e. everything at or below the level being tried
+    let (mut dark, mut dark_sum) = (0u64, 0.0);
+    let (mut best, mut best_variance) = (0, 0.0);
+    for (level, &n) in hist.iter().enumerate() {
+        dark += n;
+        dark_sum += level as f64 * n as f64;
+        let light = total - dark;
+        if dark == 0 {
+            continue;
+        }
+        if light == 0 {
+            break;
+        }
+
+        let mean_diff = dark_sum / dark as f64 - (sum - dark_sum) / light as f64;
+        let variance = dark as f64 * light as f64 * mean_diff * mean_diff;
+        if variance > best_variance {
+            best = level;
+            best_variance = variance;
+        }
+    }
+
+    // `best` is the brightest dark level, and there's always a light level above it
+    best as u8 + 1
+}
+
+/// Rounds a channel to the nearest of `levels` evenly spaced values between 0 and 255.
+#[inline]
+fn posterize(c: u8, levels: u16) -> u8 {
+    let step = 255.0 / (levels - 1) as f32;
+    ((c as f32 / step).round() * step).round() as u8
+}
+
+/// Rotates the hue of `rgb` by `hue` degrees and multiplies its saturation by `saturation`, going by way of HSL.
+#[inline]
+fn adjust_hsl(rgb: [u8; 3], hue: f32, saturation: f32) -> [u8; 3] {
+    let [r, g, b] = rgb.map(|c| c as f32 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let l = (max + min) / 2.0;
+    let d = max - min;
+
+    // Grays have no hue to rotate or saturation to scale
+    if d == 0.0 {
+        return rgb;
+    }
+
+    let s = d / (1.0 - (2.0 * l - 1.0).abs());
+    let h = 60.0
+        * if max == r {
+            ((g - b) / d).rem_euclid(6.0)
+        } else if max == g {
+            (b - r) / d + 2.0
+        } else {
+            (r - g) / d + 4.0
+        };
+
+    let h = (h + hue).rem_euclid(360.0);
+    let s = (s * saturation).min(1.0);
+
+    // And back again
+    let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+    let x = c * (1.0 - ((h / 60.0).rem_euclid(2.0) - 1.0).abs());
+    let (r, g, b) = match (h / 60.0) as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = l - c / 2.0;
+    [r, g, b].map(|v| ((v + m) * 255.0).round().clamp(0.0, 255.0) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_size_accepts_valid_dimensions() {
+        assert_eq!(parse_size("64x32"), Ok((64, 32)));
+        assert_eq!(parse_size("1x1"), Ok((1, 1)));
+    }
+
+    #[test]
+    fn parse_scale_accepts_uniform_and_separate_factors() {
+        assert_eq!(parse_scale("2"), Ok((2.0, 2.0)));
+        assert_eq!(parse_scale("2x1.5"), Ok((2.0, 1.5)));
+        assert_eq!(parse_scale("-1x1"), Ok((-1.0, 1.0)));
+        assert!(parse_scale("0x1").is_err());
+        assert!(parse_scale("1xinf").is_err());
+        assert!(parse_scale("1x2x3").is_err());
+        assert!(parse_scale("ax1").is_err());
+    }
+
+    #[test]
+    fn parse_size_rejects_wrong_component_count() {
+        assert!(parse_size("64").is_err());
+        assert!(parse_size("64x32x16").is_err());
+        assert!(parse_size("").is_err());
+    }
+
+    #[test]
+    fn parse_size_rejects_non_numeric_components() {
+        assert!(parse_size("axb").is_err());
+        assert!(parse_size("10x-5").is_err());
+        assert!(parse_size("10x").is_err());
+        assert!(parse_size("1.5x2").is_err());
+    }
+
+    #[test]
+    fn transform_rejects_invalid_scale() {
+        for scale in [
+            (0.0, 0.0),
+            (-0.0, 1.0),
+            (1.0, f32::NAN),
+            (f32::INFINITY, 1.0),
+        ] {
+            let mut frames = vec![Image::new(4, 4)];
+            let config = TransformConfig {
+                scale: Some(scale),
+                ..TransformConfig::default()
+            };
+            assert!(transform_frames(&mut frames, &config).is_err());
+        }
+    }
+
+    #[test]
+    fn transform_keeps_tiny_scales_visible() {
+        let mut frames = vec![Image::new(4, 4)];
+        let config = TransformConfig {
+            scale: Some((0.001, 0.001)),
+            preserve_dims: true,
+            ..TransformConfig::default()
+        };
+        transform_frames(&mut frames, &config).unwrap();
+        assert_eq!(frames[0].dimensions(), (1, 1));
+    }
+
+    #[test]
+    fn transform_clamps_to_max_width() {
+        let mut frames = vec![Image::new(40, 20)];
+        let config = TransformConfig {
+            size: Some((200, 50)),
+            max_width: Some(80),
+            ..TransformConfig::default()
+        };
+        transform_frames(&mut frames, &config).unwrap();
+        assert_eq!(frames[0].dimensions(), (80, 20));
+    }
+
+    #[test]
+    fn transform_pads_to_a_centered_square() {
+        let mut frames = vec![Image::from_pixel(4, 2, Rgba([255, 0, 0, 255]))];
+        let config = TransformConfig {
+            preserve_dims: true,
+            square: true,
+            ..TransformConfig::default()
+        };
+        transform_frames(&mut frames, &config).unwrap();
+
+        assert_eq!(frames[0].dimensions(), (4, 4));
+        let opaque: Vec<bool> = (0..4).map(|y| frames[0].get_pixel(0, y)[3] > 0).collect();
+        assert_eq!(opaque, [false, true, true, false]);
+
+        // Still has to fit in the terminal once it's padded out
+        let mut frames = vec![Image::new(4, 8)];
+        let config = TransformConfig {
+            max_width: Some(2),
+            ..config
+        };
+        transform_frames(&mut frames, &config).unwrap();
+        assert_eq!(frames[0].dimensions(), (2, 2));
+    }
+
+    #[test]
+    fn mirror_reflects_into_each_quadrant() {
+        let (a, b) = (Rgba([255, 0, 0, 255]), Rgba([0, 0, 255, 255]));
+        let mut frame = Image::from_pixel(2, 1, a);
+        frame.put_pixel(1, 0, b);
+        let mut frames = vec![frame];
+        let config = TransformConfig {
+            preserve_dims: true,
+            mirror: true,
+            ..TransformConfig::default()
+        };
+        transform_frames(&mut frames, &config).unwrap();
+
+        assert_eq!(frames[0].dimensions(), (4, 2));
+        for y in 0..2 {
+            let row: Vec<_> = (0..4).map(|x| *frames[0].get_pixel(x, y)).collect();
+            assert_eq!(row, [a, b, b, a]);
+        }
+
+        // The whole tiling has to fit in the terminal, not just the original
+        let mut frames = vec![Image::new(8, 8)];
+        let config = TransformConfig {
+            max_width: Some(8),
+            ..config
+        };
+        transform_frames(&mut frames, &config).unwrap();
+        assert_eq!(frames[0].dimensions(), (8, 8));
+    }
+
+    #[test]
+    fn transform_rejects_zero_size() {
+        let mut frames = vec![Image::new(4, 4)];
+        let config = TransformConfig {
+            size: Some((0, 4)),
+            ..TransformConfig::default()
+        };
+        assert!(transform_frames(&mut frames, &config).is_err());
+    }
+
+    #[test]
+    fn parse_size_rejects_zero() {
+        assert!(parse_size("0x10").is_err());
+        assert!(parse_size("10x0").is_err());
+    }
+
+    #[test]
+    fn transform_corrects_cell_aspect() {
+        let mut frames = vec![Image::new(10, 10)];
+        let config = TransformConfig {
+            preserve_dims: true,
+            cell_aspect: Some(2.5),
+            ..TransformConfig::default()
+        };
+        transform_frames(&mut frames, &config).unwrap();
+        assert_eq!(frames[0].dimensions(), (10, 8));
+
+        // Exact sizes are never corrected
+        let mut frames = vec![Image::new(10, 10)];
+        let config = TransformConfig {
+            size: Some((10, 10)),
+            cell_aspect: Some(2.5),
+            ..TransformConfig::default()
+        };
+        transform_frames(&mut frames, &config).unwrap();
+        assert_eq!(frames[0].dimensions(), (10, 10));
+    }
+
+    #[test]
+    fn transform_fits_to_cols() {
+        let mut frames = vec![Image::new(200, 100)];
+        let config = TransformConfig {
+            cols: Some(40),
+            ..TransformConfig::default()
+        };
+        transform_frames(&mut frames, &config).unwrap();
+        assert_eq!(frames[0].dimensions(), (40, 20));
+    }
+
+    #[test]
+    fn transform_fits_size_without_distorting() {
+        let frame = Image::from_pixel(20, 10, Rgba([255, 0, 0, 255]));
+        let config = |fit| TransformConfig {
+            size: Some((10, 10)),
+            fit,
+            ..TransformConfig::default()
+        };
+
+        // Letterboxed: a 10x5 strip in the middle, with transparent bars above and below
+        let mut frames = vec![frame.clone()];
+        transform_frames(&mut frames, &config(Fit::Contain)).unwrap();
+        assert_eq!(frames[0].dimensions(), (10, 10));
+        assert_eq!(frames[0].get_pixel(5, 0)[3], 0);
+        assert_eq!(frames[0].get_pixel(5, 5), &Rgba([255, 0, 0, 255]));
+        assert_eq!(frames[0].get_pixel(5, 9)[3], 0);
+
+        // Cropped: scaled up to 20x10 and the sides cut off, so nothing is left transparent
+        let mut frames = vec![frame];
+        transform_frames(&mut frames, &config(Fit::Cover)).unwrap();
+        assert_eq!(frames[0].dimensions(), (10, 10));
+        assert!(frames[0].pixels().all(|p| p[3] == 255));
+    }
+
+    /// Red, green, blue and white: the corners of `corners`, in reading order.
+    const CORNERS: [[u8; 4]; 4] = [
+        [255, 0, 0, 255],
+        [0, 255, 0, 255],
+        [0, 0, 255, 255],
+        [255, 255, 255, 255],
+    ];
+
+    /// A 2x2 frame with a different color in each corner.
+    fn corners() -> Image {
+        Image::from_fn(2, 2, |x, y| Rgba(CORNERS[(y * 2 + x) as usize]))
+    }
+
+    /// Runs `corners` through `config`, keeping its size unless `config` says otherwise, and lists its pixels in order.
+    fn transformed_corners(config: TransformConfig) -> Vec<[u8; 4]> {
+        let mut frames = vec![corners()];
+        let config = TransformConfig {
+            preserve_dims: true,
+            ..config
+        };
+        transform_frames(&mut frames, &config).unwrap();
+        frames[0].pixels().map(|p| p.0).collect()
+    }
+
+    #[test]
+    fn transform_moves_pixels_exactly() {
+        let [r, g, b, w] = CORNERS;
+
+        assert_eq!(
+            transformed_corners(TransformConfig::default()),
+            [r, g, b, w]
+        );
+        assert_eq!(
+            transformed_corners(TransformConfig {
+                flip_h: true,
+                ..TransformConfig::default()
+            }),
+            [g, r, w, b]
+        );
+        assert_eq!(
+            transformed_corners(TransformConfig {
+                flip_v: true,
+                ..TransformConfig::default()
+            }),
+            [b, w, r, g]
+        );
+        assert_eq!(
+            transformed_corners(TransformConfig {
+                flip_h: true,
+                flip_v: true,
+                ..TransformConfig::default()
+            }),
+            [w, b, g, r]
+        );
+        // Negative factors flip as well, cancelling out a flip asked for directly
+        assert_eq!(
+            transformed_corners(TransformConfig {
+                scale: Some((-1.0, -1.0)),
+                ..TransformConfig::default()
+            }),
+            [w, b, g, r]
+        );
+        assert_eq!(
+            transformed_corners(TransformConfig {
+                flip_h: true,
+                scale: Some((-1.0, 1.0)),
+                ..TransformConfig::default()
+            }),
+            [r, g, b, w]
+        );
+
+        // Nearest neighbor scaling turns every pixel into a solid block
+        assert_eq!(
+            transformed_corners(TransformConfig {
+                scale: Some((2.0, 2.0)),
+                ..TransformConfig::default()
+            }),
+            [r, r, g, g, r, r, g, g, b, b, w, w, b, b, w, w]
+        );
+        // Shrinking samples the middle of each block, which lands on its right-hand pixel
+        assert_eq!(
+            transformed_corners(TransformConfig {
+                size: Some((1, 2)),
+                ..TransformConfig::default()
+            }),
+            [g, w]
+        );
+    }
+
+    #[test]
+    fn transform_rejects_extreme_filters() {
+        for (sharpen, blur) in [(Some(0.0), None), (None, Some(-1.0)), (None, Some(1000.0))] {
+            let mut frames = vec![Image::new(4, 4)];
+            let config = TransformConfig {
+                sharpen,
+                blur,
+                ..TransformConfig::default()
+            };
+            assert!(transform_frames(&mut frames, &config).is_err());
+        }
+    }
+
+    #[test]
+    fn hsl_adjustments() {
+        assert_eq!(adjust_hsl([255, 0, 0], 120.0, 1.0), [0, 255, 0]);
+        assert_eq!(adjust_hsl([255, 0, 0], -120.0, 1.0), [0, 0, 255]);
+        assert_eq!(adjust_hsl([200, 100, 50], 0.0, 1.0), [200, 100, 50]);
+
+        // No saturation leaves only the lightness
+        let [r, g, b] = adjust_hsl([200, 100, 50], 0.0, 0.0);
+        assert!(r == g && g == b);
+    }
+
+    #[test]
+    fn posterize_snaps_to_even_levels() {
+        assert_eq!(
+            [0, 63, 64, 191, 192, 255].map(|c| posterize(c, 2)),
+            [0, 0, 0, 255, 255, 255]
+        );
+        assert_eq!(
+            [0, 42, 43, 127, 128, 255].map(|c| posterize(c, 4)),
+            [0, 0, 85, 85, 170, 255]
+        );
+        assert!((0..=255).all(|c| posterize(c, 256) == c));
+    }
+
+    #[test]
+    fn auto_threshold_splits_two_tones() {
+        let mut frame = Image::from_pixel(4, 4, Rgba([40, 40, 40, 255]));
+        for x in 0..4 {
+            frame.put_pixel(x, 0, Rgba([200, 200, 200, 255]));
+        }
+        let level = otsu_level(&frame);
+        assert!(40 < level && level <= 200);
+
+        let mut frames = vec![frame];
+        let config = TransformConfig {
+            preserve_dims: true,
+            threshold: Some(Threshold::Auto),
+            ..TransformConfig::default()
+        };
+        transform_frames(&mut frames, &config).unwrap();
+        assert_eq!(frames[0].get_pixel(0, 0), &Rgba([255, 255, 255, 255]));
+        assert_eq!(frames[0].get_pixel(0, 3), &Rgba([0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn pixelate_averages_blocks_and_edges() {
+        let mut frame = Image::from_fn(5, 2, |x, _| match x % 2 {
+            0 => Rgba([200, 0, 0, 255]),
+            _ => Rgba([0, 0, 100, 255]),
+        });
+        pixelate(&mut frame, 2);
+
+        assert_eq!(frame.get_pixel(0, 0), &Rgba([100, 0, 50, 255]));
+        assert_eq!(frame.get_pixel(3, 1), &Rgba([100, 0, 50, 255]));
+        // The last column is a block of its own
+        assert_eq!(frame.get_pixel(4, 0), &Rgba([200, 0, 0, 255]));
+    }
+
+    #[test]
+    fn sobel_finds_edges_without_going_out_of_bounds() {
+        let frame = Image::from_fn(6, 3, |x, _| match x < 3 {
+            true => Rgba([0, 0, 0, 255]),
+            false => Rgba([255, 255, 255, 255]),
+        });
+        let edges = sobel(&frame);
+        assert_eq!(edges.get_pixel(0, 0), &Rgba([0, 0, 0, 255]));
+        assert_eq!(edges.get_pixel(2, 1), &Rgba([255, 255, 255, 255]));
+        assert_eq!(edges.get_pixel(5, 2), &Rgba([0, 0, 0, 255]));
+
+        assert_eq!(sobel(&Image::new(1, 1)).dimensions(), (1, 1));
+    }
+
+    #[test]
+    fn fit_within_shrinks_to_the_tighter_side() {
+        assert_eq!(fit_within((64, 32), (80, 48)), None);
+        assert_eq!(fit_within((64, 32), (32, 48)), Some((32, 16)));
+        assert_eq!(fit_within((64, 32), (80, 8)), Some((16, 8)));
+        // Never shrinks away to nothing
+        assert_eq!(fit_within((64, 32), (0, 0)), Some((1, 1)));
+    }
+}
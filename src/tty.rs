@@ -0,0 +1,75 @@
+#[cfg(unix)]
+use std::{
+    fs::OpenOptions,
+    io::{self, IsTerminal, Write},
+    os::fd::AsRawFd,
+    path::Path,
+};
+
+use crossterm::terminal;
+
+#[cfg(unix)]
+use crate::error::Png2tError;
+
+/// Draws on the terminal device at `path` (such as `/dev/pts/3`) instead of the one png2t was started from, and reads keys from it.
+///
+/// Everything png2t draws goes through stdout, and crossterm reads keys (and switches raw mode) through stdin whenever it's a
+/// terminal, so pointing both of those at the device moves all of it over at once. Warnings still go to stderr as usual.
+///
+/// # Errors
+/// Fails if `path` can't be opened for reading and writing, or isn't a terminal.
+#[cfg(unix)]
+pub fn use_tty(path: &Path) -> Result<(), Png2tError> {
+    let tty = match OpenOptions::new().read(true).write(true).open(path) {
+        Ok(f) => f,
+        Err(e) => {
+            return Err(Png2tError::io(
+                format!("Unable to open {}", path.display()),
+                e,
+            ))
+        }
+    };
+    if !tty.is_terminal() {
+        return Err(Png2tError::InvalidArgs(format!(
+            "{} is not a terminal",
+            path.display()
+        )));
+    }
+
+    // Anything already written should still go where it was meant to
+    let _ = io::stdout().flush();
+    for fd in [libc::STDIN_FILENO, libc::STDOUT_FILENO] {
+        // SAFETY: both descriptors are open, and replacing stdin/stdout doesn't invalidate anything Rust holds onto
+        if unsafe { libc::dup2(tty.as_raw_fd(), fd) } == -1 {
+            return Err(Png2tError::io(
+                format!("Unable to draw on {}", path.display()),
+                io::Error::last_os_error(),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// The size of the terminal being drawn on, in columns and rows.
+///
+/// crossterm asks the terminal png2t was started from, which isn't the one being drawn on after `use_tty`,
+/// so stdout is asked first.
+pub fn terminal_size() -> Option<(u16, u16)> {
+    #[cfg(unix)]
+    {
+        let mut size = libc::winsize {
+            ws_row: 0,
+            ws_col: 0,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        // SAFETY: TIOCGWINSZ only writes into `size`, and fails harmlessly if stdout isn't a terminal
+        let res = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+        if res == 0 && size.ws_col > 0 && size.ws_row > 0 {
+            return Some((size.ws_col, size.ws_row));
+        }
+    }
+
+    terminal::size().ok()
+}